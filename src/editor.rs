@@ -1,59 +1,198 @@
-use crossterm::event::{read, Event, KeyEvent, KeyEventKind};
+use crossterm::event::{poll, read, Event, KeyEvent, KeyEventKind};
 use std::{
+    cell::RefCell,
+    collections::HashMap,
     io::Error,
-    panic::{set_hook,take_hook},};
+    panic::{set_hook,take_hook},
+    rc::Rc,
+    time::Duration,};
 mod terminal;
 mod statusbar;
 mod view;
 mod editorcommand;
+mod bufferlist;
+mod layout;
+mod window;
+mod tabpage;
+mod messageline;
+mod tabline;
+mod replay;
+mod theme;
+#[cfg(test)]
+mod tests;
 
-use statusbar::StatusBar;
-use view::{Bmode, View};
-use terminal::Terminal;
+use bufferlist::BufferList;
+pub use editorcommand::{Direction, EditorCommand};
+use layout::{Rect, Split};
+use messageline::{MessageLine, Severity};
+pub use replay::parse_keys;
+use tabline::TabLine;
+use tabpage::TabPage;
+use view::{DiskChange, Request, View};
+pub use view::{Buffer, Line, LineEnding, Location, Motion};
+use terminal::{BackendGuard, HeadlessBackend, Terminal};
+pub use terminal::{Coords, Size};
+use theme::Theme;
 
 #[derive(Default, Eq, PartialEq, Debug)]
 pub struct DocumentStatus {
     total_lines: usize,
     current_line_index: usize,
+    /// Counted in graphemes from the start of the line.
+    grapheme_column: usize,
+    /// Counted in screen columns, tabs and wide characters taking up more
+    /// than one.
+    display_column: usize,
+    is_indexing: bool,
     is_modified: bool,
+    is_read_only: bool,
     filename: String,
-    bmode_string: String
+    filetype: &'static str,
+    encoding: &'static str,
+    line_ending: &'static str,
+    bmode_string: String,
+    /// The count and keys of a command still being typed.
+    pending_keys: String,
+    /// The register keys are being recorded into.
+    recording: Option<char>,
+    /// The format the status line is drawn in, from the `statusline` option.
+    format: String,
 }
 
+const NAME: &str = env!("CARGO_PKG_NAME");
+
+/// How long to wait for input before refreshing anyway, so that work done in
+/// the background (such as indexing a large file) shows up on screen.
+const IDLE_REFRESH: Duration = Duration::from_millis(250);
+
+/// How deep macros may play one another, so that one playing itself
+/// comes to an end.
+const MAX_PLAY_DEPTH: usize = 100;
+
 pub struct Editor {
     should_quit:  bool,
-    view: View,
-    statusbar: StatusBar,
+    buffers: BufferList,
+    tabs: Vec<TabPage>,
+    current_tab: usize,
+    tabline: TabLine,
+    messageline: MessageLine,
+    size: Size,
+    /// Set when the current tab page has to be given its part of the screen
+    /// again: after resizing, switching tab pages, or the tab line coming or
+    /// going.
+    needs_layout: bool,
+    /// The register keys are being recorded into, and the keys so far.
+    recording: Option<(char, Vec<Event>)>,
+    /// The keys recorded into each register.
+    registers: HashMap<char, Vec<Event>>,
+    /// The register `@@` plays again.
+    last_played: Option<char>,
+    /// How many macros are being played, one from within the other.
+    play_depth: usize,
+    /// The backend a headless editor put in place of the terminal, given
+    /// back when it is dropped.
+    backend: Option<BackendGuard>,
 }
 
 impl Editor {
-    pub fn new() -> Result<Self, Error> {
+    /// Takes over the terminal, on the alternate screen in raw mode, and
+    /// opens `filenames` in it. The terminal is given back when the editor
+    /// is dropped, or if it panics, in which case the swap files are brought
+    /// up to date first.
+    ///
+    /// # Errors
+    ///
+    /// If the terminal cannot be set up.
+    pub fn new(filenames: &[String]) -> Result<Self, Error> {
         let current_hook = take_hook();
         set_hook(Box::new(move|panic_info| {
+            BufferList::flush_swaps();
             let _ = Terminal::terminate();
             current_hook(panic_info);
         }));
         Terminal::initialize()?;
+        Ok(Self::open(filenames, Terminal::size().unwrap_or_default()))
+    }
+
+    /// An editor drawing to a screen of `size` kept in memory rather than
+    /// to the terminal, for driving it from code with `execute`.
+    #[must_use]
+    pub fn headless(filenames: &[String], size: Size) -> Self {
+        let (backend, _) = HeadlessBackend::new(size);
+        let backend = Terminal::use_backend(Box::new(backend));
+        let mut editor = Self::open(filenames, size);
+        editor.backend = Some(backend);
+        editor.refresh_screen();
+        editor
+    }
+
+    /// An editor on a screen of `size`, showing the first of `filenames`.
+    fn open(filenames: &[String], size: Size) -> Self {
+        Terminal::resize(size);
         let mut view = View::default();
-        let args: Vec<String> = std::env::args().collect();
-        if let Some(filename) = args.get(1) {
-            view.load(filename);
+        let mut buffers = BufferList::default();
+        let mut messageline = MessageLine::new(size.height.saturating_sub(1), size.width);
+        for filename in filenames {
+            match Buffer::open(filename) {
+                Ok(buffer) => {
+                    buffers.add(Rc::new(RefCell::new(buffer)));
+                },
+                Err(err) => messageline.show(Severity::Error, format!("Could not open {filename}: {err}")),
+            }
+        }
+        match buffers.get(0) {
+            Some((buffer, position)) => view.show_buffer(buffer, position),
+            None => {
+                buffers.add(view.buffer());
+            },
         }
-        Ok(Self {
+        Self {
             should_quit: false,
-            view,
-            statusbar: StatusBar::new(),
-        })
+            buffers,
+            tabs: vec![TabPage::new(view)],
+            current_tab: 0,
+            tabline: TabLine::new(size.width),
+            messageline,
+            size,
+            needs_layout: true,
+            recording: None,
+            registers: HashMap::new(),
+            last_played: None,
+            play_depth: 0,
+            backend: None,
+        }
+    }
+
+    fn tab(&self) -> &TabPage {
+        &self.tabs[self.current_tab]
+    }
+
+    fn tab_mut(&mut self) -> &mut TabPage {
+        &mut self.tabs[self.current_tab]
+    }
+
+    fn view(&self) -> &View {
+        self.tab().view()
+    }
+
+    fn view_mut(&mut self) -> &mut View {
+        self.tab_mut().view_mut()
     }
 
+    /// Handles keys until the editor quits.
+    ///
+    /// # Panics
+    ///
+    /// In debug builds, if events cannot be read from the terminal.
     pub fn run(&mut self){
         loop {
             self.refresh_screen();
             if self.should_quit {
                 break;
             }
-            match read() {
-                Ok(event) => self.evaluate_event(event),
+            match poll(IDLE_REFRESH).and_then(|ready| ready.then(read).transpose()) {
+                Ok(Some(event)) => self.evaluate_event(event),
+                Ok(None) => {},
                 Err(err) => {
                     #[cfg(debug_assertions)]
                     {
@@ -61,43 +200,503 @@ impl Editor {
                     }
                 }
             }
-            let status= self.view.get_status();
-            self.statusbar.update_status(status);
+            self.write_swaps();
+        }
+    }
+
+    /// Looks for files changed on disk behind the editor's back, as happens
+    /// while the terminal is out of focus. The first view showing a buffer
+    /// deals with it, the current one going first. Hidden buffers are
+    /// reloaded if clean, and otherwise ask what to do once shown again.
+    fn check_disk_changes(&mut self) {
+        self.view_mut().check_disk_changes();
+        let mut checked = vec![self.view().buffer()];
+        for tab in &mut self.tabs {
+            for window in tab.windows_mut() {
+                let buffer = window.view.buffer();
+                if !checked.iter().any(|seen| Rc::ptr_eq(seen, &buffer)) {
+                    window.view.check_disk_changes();
+                    checked.push(buffer);
+                }
+            }
+        }
+        for buffer in self.buffers.buffers() {
+            if checked.iter().any(|seen| Rc::ptr_eq(seen, buffer)) {
+                continue;
+            }
+            let mut buffer = buffer.borrow_mut();
+            match buffer.disk_change() {
+                DiskChange::Unchanged => {},
+                DiskChange::Deleted => {
+                    buffer.acknowledge_disk_state();
+                    self.messageline.show(Severity::Warning, format!("\"{}\" was deleted on disk", buffer.filename()));
+                },
+                DiskChange::Modified if buffer.is_dirty() => {
+                    self.messageline.show(Severity::Warning, format!("\"{}\" changed on disk", buffer.filename()));
+                },
+                DiskChange::Modified => {
+                    buffer.reload();
+                },
+            }
+        }
+    }
+
+    fn write_swaps(&self) {
+        for buffer in self.buffers.buffers() {
+            buffer.borrow_mut().write_swap();
         }
     }
 
-    #[allow(clippy::needless_pass_by_value)]
     fn evaluate_event(&mut self, event: Event) {
-        let should_process = match &event {
-            Event::Key(KeyEvent {kind, .. }) => kind == &KeyEventKind::Press,
-            Event::Resize(_,_) => true,
-            _ => false,
+        match event {
+            Event::FocusGained => self.check_disk_changes(),
+            Event::Resize(..) => {
+                if let Ok(EditorCommand::Resize(size)) = EditorCommand::try_from(event) {
+                    self.size = size;
+                    Terminal::resize(size);
+                    self.tabline.resize(size.width);
+                    self.messageline.resize(size.height.saturating_sub(1), size.width);
+                    self.needs_layout = true;
+                }
+            },
+            Event::Mouse(_) => {
+                if let Ok(command) = EditorCommand::try_from(event) {
+                    self.tab_mut().handle_mouse(command);
+                }
+            },
+            Event::Key(KeyEvent { kind: KeyEventKind::Press, .. }) | Event::Paste(_) => {
+                if let Some((_, keys)) = &mut self.recording
+                    && self.play_depth == 0 {
+                    keys.push(event.clone());
+                }
+                if let Some(request) = self.view_mut().handle_command(event) {
+                    self.handle_request(request);
+                }
+            },
+            _ => {},
+        }
+    }
+
+    fn handle_request(&mut self, request: Request) {
+        match request {
+            Request::Quit => self.quit(false),
+            Request::Command(command) => self.execute_command(&command),
+            Request::Window(key) => self.window_command(key),
+            Request::Record(register) => self.record(register),
+            Request::Play { register, count } => self.play(register, count),
+        }
+    }
+
+    /// Runs `command` as if it was typed after `:`, as in
+    /// `editor.execute("split notes.txt")`, leaving the current window in
+    /// Normal mode. What goes wrong shows on the message line, as it does
+    /// when typed.
+    pub fn execute(&mut self, command: &str) {
+        if let Some(request) = self.view_mut().run_command_line(command) {
+            self.handle_request(request);
+        }
+        self.refresh_screen();
+    }
+
+    /// The buffer shown in the current window.
+    #[must_use]
+    pub fn buffer(&self) -> Rc<RefCell<Buffer>> {
+        self.view().buffer()
+    }
+
+    /// Starts recording keys into `register`, or with `None` stops and
+    /// keeps the keys typed, leaving out the `q` that stopped it.
+    fn record(&mut self, register: Option<char>) {
+        match register {
+            Some(register) => self.recording = Some((register, Vec::new())),
+            None => {
+                if let Some((register, mut keys)) = self.recording.take() {
+                    keys.pop();
+                    self.registers.insert(register, keys);
+                }
+            },
+        }
+        for window in self.tabs.iter_mut().flat_map(TabPage::windows_mut) {
+            window.view.set_recording(register);
+        }
+    }
+
+    /// Types the keys recorded into `register` again, `count` times.
+    fn play(&mut self, register: char, count: usize) {
+        let register = if register == '@' { self.last_played } else { Some(register) };
+        let Some(keys) = register.and_then(|register| self.registers.get(&register)).cloned() else {
+            return;
         };
+        self.last_played = register;
+        if self.play_depth == MAX_PLAY_DEPTH {
+            return;
+        }
+        self.play_depth += 1;
+        for event in (0..count).flat_map(|_| keys.iter().cloned()) {
+            if self.should_quit {
+                break;
+            }
+            self.evaluate_event(event);
+        }
+        self.play_depth -= 1;
+    }
+
+    /// Quits, unless a buffer has changes that were not written and `force`
+    /// is not given. The swap files go, as nothing is left unsaved or the
+    /// changes were thrown away on purpose.
+    fn quit(&mut self, force: bool) {
+        let dirty = self.buffers.buffers().find(|buffer| buffer.borrow().is_dirty());
+        if let Some(buffer) = dirty
+            && !force {
+            let name = buffer.borrow().filename().to_string();
+            self.messageline.show(Severity::Error, format!("No write since last change for {name} (add ! to override)"));
+            return;
+        }
+        for buffer in self.buffers.buffers() {
+            buffer.borrow_mut().discard_swap();
+        }
+        self.should_quit = true;
+    }
 
-        if should_process {
-            if self.view.handle_command(event) {
-                self.should_quit = true;
+    /// Runs the `:` commands that go beyond the current view. A trailing `!`
+    /// on the command name forces it.
+    fn execute_command(&mut self, command: &str) {
+        let command = command.trim();
+        let name_len = command.find(|c: char| !c.is_ascii_alphabetic()).unwrap_or(command.len());
+        let (name, rest) = command.split_at(name_len);
+        let force = rest.starts_with('!');
+        let argument = rest.trim_start_matches('!').trim();
+        match name {
+            "w" | "write" => self.view_mut().save(),
+            "q" | "quit" => self.quit_window(force),
+            "qa" | "qall" | "quitall" => self.quit(force),
+            "wq" | "x" => {
+                self.view_mut().save();
+                if !self.view().buffer().borrow().is_dirty() {
+                    self.quit_window(false);
+                }
+            },
+            "e" | "edit" if !argument.is_empty() => self.edit(argument),
+            "ls" | "buffers" => {
+                let listing = self.buffers.listing(self.current_buffer());
+                self.view_mut().show_info(listing);
+            },
+            "mes" | "messages" => {
+                let history = self.messageline.history().to_vec();
+                self.view_mut().show_info(history);
+            },
+            "bn" | "bnext" => self.cycle_buffer(1),
+            "bp" | "bprevious" => self.cycle_buffer(self.buffers.len().saturating_sub(1)),
+            "b" | "buffer" => match self.find_buffer(argument) {
+                Some(index) => self.switch_to_buffer(index),
+                None => self.messageline.show(Severity::Error, format!("No matching buffer for {argument}")),
+            },
+            "bd" | "bdelete" => self.delete_buffer(argument, force),
+            "sp" | "split" => self.split(Split::Horizontal, argument),
+            "vs" | "vsplit" => self.split(Split::Vertical, argument),
+            "clo" | "close" => self.close_window(),
+            "on" | "only" => self.tab_mut().only_window(),
+            "res" | "resize" => self.resize_window(argument),
+            "se" | "set" => {
+                if let Err(message) = self.view_mut().set_option(argument) {
+                    self.messageline.show(Severity::Error, message);
+                }
+            },
+            "colo" | "colorscheme" => self.set_colorscheme(argument),
+            "tabnew" | "tabe" | "tabedit" => self.new_tab(argument),
+            "tabc" | "tabclose" => self.close_tab(),
+            "tabo" | "tabonly" => self.only_tab(),
+            "tabn" | "tabnext" => match argument.parse::<usize>() {
+                Ok(number) if (1..=self.tabs.len()).contains(&number) => self.switch_to_tab(number - 1),
+                _ => self.switch_to_tab((self.current_tab + 1) % self.tabs.len()),
+            },
+            "tabp" | "tabprevious" | "tabN" | "tabNext" => {
+                let back = argument.parse::<usize>().unwrap_or(1) % self.tabs.len();
+                self.switch_to_tab((self.current_tab + self.tabs.len() - back) % self.tabs.len());
+            },
+            _ => self.messageline.show(Severity::Error, format!("Not an editor command: {command}")),
+        }
+    }
+
+    /// Switches to the theme called `name`, redrawing everything in its
+    /// colors, or shows the current one if no name is given.
+    fn set_colorscheme(&mut self, name: &str) {
+        if name.is_empty() {
+            self.messageline.show(Severity::Info, Theme::current_name());
+            return;
+        }
+        match Theme::load(name) {
+            Ok(theme) => {
+                theme.apply();
+                self.needs_layout = true;
+            },
+            Err(message) => self.messageline.show(Severity::Error, message),
+        }
+    }
+
+    /// Runs the window command typed after `Ctrl-w`.
+    fn window_command(&mut self, key: char) {
+        match key {
+            's' | 'S' => self.split(Split::Horizontal, ""),
+            'v' => self.split(Split::Vertical, ""),
+            'h' => self.tab_mut().focus(Direction::Left),
+            'j' => self.tab_mut().focus(Direction::Down),
+            'k' => self.tab_mut().focus(Direction::Up),
+            'l' => self.tab_mut().focus(Direction::Right),
+            'w' => self.tab_mut().cycle(1),
+            'W' => {
+                let count = self.tab().window_count();
+                self.tab_mut().cycle(count - 1);
+            },
+            'c' => self.close_window(),
+            'q' => self.quit_window(false),
+            'o' => self.tab_mut().only_window(),
+            '+' => self.tab_mut().grow_window(Split::Horizontal, 1),
+            '-' => self.tab_mut().grow_window(Split::Horizontal, -1),
+            '>' => self.tab_mut().grow_window(Split::Vertical, 1),
+            '<' => self.tab_mut().grow_window(Split::Vertical, -1),
+            '=' => self.tab_mut().equalize(),
+            _ => {},
+        }
+    }
+
+    /// Splits the current window in two, showing `filename` in the new one,
+    /// or the same buffer if no file is given.
+    fn split(&mut self, split: Split, filename: &str) {
+        if !self.tab_mut().split(split) {
+            self.messageline.show(Severity::Error, "Not enough room".to_string());
+            return;
+        }
+        if !filename.is_empty() {
+            self.edit(filename);
+        }
+    }
+
+    /// Closes the current window, or its tab page if it is the last window
+    /// there, or quits if it is the last window altogether.
+    fn quit_window(&mut self, force: bool) {
+        if self.tab().window_count() > 1 {
+            self.close_window();
+        } else if self.tabs.len() > 1 {
+            self.close_tab();
+        } else {
+            self.quit(force);
+        }
+    }
+
+    fn close_window(&mut self) {
+        if self.tab().window_count() == 1 {
+            self.messageline.show(Severity::Error, "Cannot close last window".to_string());
+            return;
+        }
+        self.remember_position();
+        self.tab_mut().close_window();
+    }
+
+    /// `:resize N` sets the height of the current window to N lines, while
+    /// `:resize +N` and `:resize -N` change it by N.
+    fn resize_window(&mut self, argument: &str) {
+        let Ok(amount) = argument.parse::<isize>() else {
+            self.messageline.show(Severity::Error, format!("Invalid argument: {argument}"));
+            return;
+        };
+        let delta = if argument.starts_with(['+', '-']) {
+            amount
+        } else {
+            let height = self.tab().window_height();
+            amount.saturating_add(1).saturating_sub_unsigned(height)
+        };
+        self.tab_mut().grow_window(Split::Horizontal, delta);
+    }
+
+    /// Opens a tab page after the current one, showing `filename` or a new
+    /// empty buffer.
+    fn new_tab(&mut self, filename: &str) {
+        let index = if filename.is_empty() {
+            Some(self.buffers.add(Rc::new(RefCell::new(Buffer::default()))))
+        } else {
+            self.open_buffer(filename)
+        };
+        let Some((buffer, position)) = index.and_then(|index| self.buffers.get(index)) else {
+            return;
+        };
+        self.remember_position();
+        let mut view = self.view().split_off();
+        view.show_buffer(buffer, position);
+        self.tabs.insert(self.current_tab + 1, TabPage::new(view));
+        self.current_tab += 1;
+        self.needs_layout = true;
+    }
+
+    fn close_tab(&mut self) {
+        if self.tabs.len() == 1 {
+            self.messageline.show(Severity::Error, "Cannot close last tab page".to_string());
+            return;
+        }
+        self.remember_position();
+        self.tabs.remove(self.current_tab);
+        self.current_tab = self.current_tab.min(self.tabs.len() - 1);
+        self.needs_layout = true;
+    }
+
+    fn only_tab(&mut self) {
+        let tab = self.tabs.swap_remove(self.current_tab);
+        self.tabs = vec![tab];
+        self.current_tab = 0;
+        self.needs_layout = true;
+    }
+
+    fn switch_to_tab(&mut self, index: usize) {
+        if index != self.current_tab {
+            self.current_tab = index;
+            self.needs_layout = true;
+        }
+    }
+
+    /// The part of the screen between the tab line, if there is one, and
+    /// the message line.
+    fn tab_area(&self) -> Rect {
+        let top = usize::from(self.tabs.len() > 1);
+        Rect {
+            origin: Coords { row: top, col: 0 },
+            size: Size { width: self.size.width, height: self.size.height.saturating_sub(top + 1) },
+        }
+    }
+
+    fn current_buffer(&self) -> Option<usize> {
+        self.buffers.index_of(&self.view().buffer())
+    }
+
+    fn remember_position(&mut self) {
+        if let Some(current) = self.current_buffer() {
+            self.buffers.remember(current, self.view().position());
+        }
+    }
+
+    /// Finds a buffer by number, as shown by `:ls`, or by name.
+    fn find_buffer(&self, argument: &str) -> Option<usize> {
+        match argument.parse::<usize>() {
+            Ok(number) => number.checked_sub(1).filter(|index| *index < self.buffers.len()),
+            Err(_) => self.buffers.find(argument),
+        }
+    }
+
+    fn edit(&mut self, filename: &str) {
+        if let Some(index) = self.open_buffer(filename) {
+            self.switch_to_buffer(index);
+        }
+    }
+
+    /// Finds the buffer for `filename` in the buffer list, adding it if it
+    /// is not there yet.
+    fn open_buffer(&mut self, filename: &str) -> Option<usize> {
+        if let Some(index) = self.buffers.find_file(filename) {
+            return Some(index);
+        }
+        match Buffer::open(filename) {
+            Ok(buffer) => Some(self.buffers.add(Rc::new(RefCell::new(buffer)))),
+            Err(err) => {
+                self.messageline.show(Severity::Error, format!("Could not open {filename}: {err}"));
+                None
+            },
+        }
+    }
+
+    fn cycle_buffer(&mut self, step: usize) {
+        let count = self.buffers.len();
+        if let Some(current) = self.current_buffer() && count > 0 {
+            self.switch_to_buffer((current + step) % count);
+        }
+    }
+
+    fn switch_to_buffer(&mut self, index: usize) {
+        self.remember_position();
+        if let Some((buffer, position)) = self.buffers.get(index) {
+            self.view_mut().show_buffer(buffer, position);
+        }
+    }
+
+    /// Removes a buffer from the list, refusing to throw away unsaved changes
+    /// unless forced. Windows showing it move on to a neighbouring buffer, or
+    /// to a new empty one if this was the last.
+    fn delete_buffer(&mut self, argument: &str, force: bool) {
+        let index = if argument.is_empty() { self.current_buffer() } else { self.find_buffer(argument) };
+        let Some((buffer, _)) = index.and_then(|index| self.buffers.get(index)) else {
+            self.messageline.show(Severity::Error, format!("No matching buffer for {argument}"));
+            return;
+        };
+        if buffer.borrow().is_dirty() && !force {
+            let name = buffer.borrow().filename().to_string();
+            self.messageline.show(Severity::Error, format!("No write since last change for {name} (add ! to override)"));
+            return;
+        }
+        if let Some(removed) = index.and_then(|index| self.buffers.remove(index)) {
+            removed.borrow_mut().discard_swap();
+        }
+        if self.buffers.len() == 0 {
+            self.buffers.add(Rc::new(RefCell::new(Buffer::default())));
+        }
+        let next = index.unwrap_or_default().min(self.buffers.len() - 1);
+        for window in self.tabs.iter_mut().flat_map(TabPage::windows_mut) {
+            if Rc::ptr_eq(&window.view.buffer(), &buffer)
+                && let Some((buffer, position)) = self.buffers.get(next) {
+                window.view.show_buffer(buffer, position);
             }
         }
     }
 
     fn refresh_screen(&mut self){
-        let _ = Terminal::hide_caret();
-        self.view.render();
-        let status= self.view.get_status();
-        self.statusbar.update_status(status);
-        self.statusbar.render();
-        let _ = Terminal::move_caret_to(self.view.caret_position());
-        let _ = Terminal::show_caret();
+        self.render();
         let _ = Terminal::execute();
     }
+
+    /// Draws the next frame: every part of the screen that changed, laid out
+    /// anew first if the screen was resized or the tab line came or went.
+    fn render(&mut self) {
+        Terminal::hide_caret();
+        if self.needs_layout {
+            let area = self.tab_area();
+            self.tab_mut().place(area);
+            self.tabline.resize(self.size.width);
+            self.messageline.resize(self.size.height.saturating_sub(1), self.size.width);
+            self.needs_layout = false;
+        }
+        if self.tabs.len() > 1 {
+            let labels = self.tabs.iter().map(TabPage::label).collect();
+            self.tabline.update(labels, self.current_tab);
+            self.tabline.render();
+        }
+        self.tab_mut().render();
+        // After the views drew, so that what a search going on in the
+        // background found shows in the same frame.
+        for window in self.tabs.iter_mut().flat_map(TabPage::windows_mut) {
+            for (severity, text) in window.view.take_messages() {
+                self.messageline.show(severity, text);
+            }
+        }
+        self.messageline.set_command_line(self.view().command_line());
+        self.messageline.render();
+        Terminal::move_caret_to(self.messageline.caret_position().unwrap_or_else(|| self.tab().caret_position()));
+        Terminal::set_caret_shape(self.view().caret_shape());
+        Terminal::set_title(&self.title());
+        Terminal::show_caret();
+    }
+
+    /// The window title: the file in the current window, marked with `[+]`
+    /// if it is modified.
+    fn title(&self) -> String {
+        let buffer = self.view().buffer();
+        let buffer = buffer.borrow();
+        let modified = if buffer.is_dirty() { " [+]" } else { "" };
+        format!("{}{modified} - {NAME}", buffer.filename())
+    }
 }
 
 impl Drop for Editor {
     fn drop(&mut self) {
         let _ = Terminal::terminate();
         if self.should_quit {
-            let _ = Terminal::print(Some("Goodbye\r\n"),None,None);
+            let _ = Terminal::print("Goodbye\r\n");
         }
     }
 }
@@ -0,0 +1,132 @@
+use std::cell::RefCell;
+use std::fs::canonicalize;
+use std::path::{absolute, Path, PathBuf};
+use std::rc::{Rc, Weak};
+use super::view::{Buffer, Position, Swap};
+
+thread_local! {
+    /// Every buffer added to a list, so that the panic hook can get at them
+    /// without the editor.
+    static OPEN_BUFFERS: RefCell<Vec<Weak<RefCell<Buffer>>>> = const { RefCell::new(Vec::new()) };
+}
+
+struct Entry {
+    buffer: Rc<RefCell<Buffer>>,
+    position: Position,
+}
+
+/// Every buffer open in the editor, in the order they were opened, with the
+/// position each one was last viewed at. Buffers are numbered from 1.
+#[derive(Default)]
+pub struct BufferList {
+    entries: Vec<Entry>,
+}
+
+impl BufferList {
+    pub fn add(&mut self, buffer: Rc<RefCell<Buffer>>) -> usize {
+        OPEN_BUFFERS.with_borrow_mut(|open| {
+            open.retain(|buffer| buffer.strong_count() > 0);
+            open.push(Rc::downgrade(&buffer));
+        });
+        self.entries.push(Entry { buffer, position: Position::default() });
+        self.entries.len() - 1
+    }
+
+    /// Writes the swap file of every open buffer with unsaved edits. Those
+    /// the panic left borrowed get what was last kept for them.
+    pub fn flush_swaps() {
+        let _ = OPEN_BUFFERS.try_with(|open| {
+            let Ok(open) = open.try_borrow() else { return };
+            for buffer in open.iter().filter_map(Weak::upgrade) {
+                if let Ok(buffer) = buffer.try_borrow() {
+                    buffer.flush_swap();
+                }
+            }
+        });
+        Swap::flush_pending();
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn get(&self, index: usize) -> Option<(Rc<RefCell<Buffer>>, Position)> {
+        self.entries
+            .get(index)
+            .map(|entry| (Rc::clone(&entry.buffer), entry.position))
+    }
+
+    pub fn buffers(&self) -> impl Iterator<Item = &Rc<RefCell<Buffer>>> {
+        self.entries.iter().map(|entry| &entry.buffer)
+    }
+
+    pub fn index_of(&self, buffer: &Rc<RefCell<Buffer>>) -> Option<usize> {
+        self.entries.iter().position(|entry| Rc::ptr_eq(&entry.buffer, buffer))
+    }
+
+    /// Looks up the buffer editing `filename`, however the path to it is
+    /// written.
+    pub fn find_file(&self, filename: &str) -> Option<usize> {
+        let path = Self::canonical(filename);
+        self.entries
+            .iter()
+            .position(|entry| Self::canonical(entry.buffer.borrow().filename()) == path)
+    }
+
+    /// The absolute path of `filename` with links resolved, or as close to it
+    /// as can be worked out for a file not created yet.
+    fn canonical(filename: &str) -> PathBuf {
+        let path = Path::new(filename);
+        canonicalize(path)
+            .or_else(|_| absolute(path))
+            .unwrap_or_else(|_| path.to_path_buf())
+    }
+
+    /// Looks a buffer up by its file name, or failing that by a part of the
+    /// name that only one buffer matches. Meant for picking a buffer by
+    /// hand, as with `:b`; `find_file` is for opening files.
+    pub fn find(&self, name: &str) -> Option<usize> {
+        let exact = self.entries.iter().position(|entry| entry.buffer.borrow().filename() == name);
+        exact.or_else(|| {
+            let mut matches = self
+                .entries
+                .iter()
+                .enumerate()
+                .filter(|(_, entry)| entry.buffer.borrow().filename().contains(name));
+            match (matches.next(), matches.next()) {
+                (Some((index, _)), None) => Some(index),
+                _ => None,
+            }
+        })
+    }
+
+    pub fn remember(&mut self, index: usize, position: Position) {
+        if let Some(entry) = self.entries.get_mut(index) {
+            entry.position = position;
+        }
+    }
+
+    pub fn remove(&mut self, index: usize) -> Option<Rc<RefCell<Buffer>>> {
+        (index < self.entries.len()).then(|| self.entries.remove(index).buffer)
+    }
+
+    /// One line per buffer the way `:ls` shows them: number, `%` for the
+    /// current one, `+` if modified, name and last line.
+    pub fn listing(&self, current: Option<usize>) -> Vec<String> {
+        self.entries
+            .iter()
+            .enumerate()
+            .map(|(index, entry)| {
+                let buffer = entry.buffer.borrow();
+                let marker = if Some(index) == current { '%' } else { ' ' };
+                let modified = if buffer.is_dirty() { '+' } else { ' ' };
+                format!(
+                    "{:>3} {marker}{modified} \"{}\"  line {}",
+                    index + 1,
+                    buffer.filename(),
+                    entry.position.text_location.line_index + 1
+                )
+            })
+            .collect()
+    }
+}
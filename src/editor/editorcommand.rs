@@ -1,6 +1,6 @@
-use crossterm::event::{Event, KeyCode, KeyEvent, KeyModifiers};
+use crossterm::event::{Event, KeyCode, KeyEvent, KeyModifiers, MouseButton, MouseEvent, MouseEventKind};
 use std::convert::TryFrom;
-use super::terminal::Size;
+use super::terminal::{Coords, Size};
 
 #[derive(Clone, Copy)]
 pub enum Direction {
@@ -13,6 +13,7 @@ pub enum Direction {
 #[derive(Clone, Copy)]
 pub enum EditorCommand {
     Key(char),
+    Control(char),
     Resize(Size),
     Escape,
     Other,
@@ -24,6 +25,12 @@ pub enum EditorCommand {
     Right,
     Tab,
     Enter,
+    /// The left button pressed over a cell of the screen.
+    Click(Coords),
+    /// The pointer moved to a cell with the left button held down.
+    Drag(Coords),
+    /// The wheel turned up or down with the pointer over a cell.
+    Scroll(Direction, Coords),
 }
 
 impl TryFrom<Event> for EditorCommand {
@@ -35,6 +42,7 @@ impl TryFrom<Event> for EditorCommand {
             }) => match (code, modifiers) {
                 (KeyCode::Char(_), KeyModifiers::NONE | KeyModifiers::SHIFT) => 
                     if let KeyCode::Char(c) = code {Ok(Self::Key(c))} else {Ok(Self::Other)},
+                (KeyCode::Char(c), KeyModifiers::CONTROL) => Ok(Self::Control(c)),
                 (KeyCode::Esc, _) => Ok(Self::Escape),
                 (KeyCode::Delete, _) => Ok(Self::Delete),
                 (KeyCode::Backspace, _) => Ok(Self::Backspace),
@@ -53,6 +61,16 @@ impl TryFrom<Event> for EditorCommand {
                 let width = width_u16 as usize;
                 Ok(Self::Resize(Size {width, height}))
             },
+            Event::Mouse(MouseEvent { kind, column, row, .. }) => {
+                let at = Coords { row: usize::from(row), col: usize::from(column) };
+                match kind {
+                    MouseEventKind::Down(MouseButton::Left) => Ok(Self::Click(at)),
+                    MouseEventKind::Drag(MouseButton::Left) => Ok(Self::Drag(at)),
+                    MouseEventKind::ScrollUp => Ok(Self::Scroll(Direction::Up, at)),
+                    MouseEventKind::ScrollDown => Ok(Self::Scroll(Direction::Down, at)),
+                    _ => Ok(Self::Other),
+                }
+            },
             _ => Err(format!("unsupported event: {event:?}")),
         }
     }
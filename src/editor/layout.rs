@@ -0,0 +1,261 @@
+use super::{
+    editorcommand::Direction,
+    terminal::{Coords, Size},
+};
+
+/// Windows are never made smaller than this many rows or columns, counting
+/// the status line.
+pub const MIN_SIZE: usize = 2;
+
+#[derive(Copy, Clone, PartialEq, Eq)]
+pub enum Split {
+    /// Windows stacked on top of each other, as made by `:split`.
+    Horizontal,
+    /// Windows side by side with a separator column between them, as made by
+    /// `:vsplit`.
+    Vertical,
+}
+
+#[derive(Copy, Clone, Default, PartialEq, Eq, Debug)]
+pub struct Rect {
+    pub origin: Coords,
+    pub size: Size,
+}
+
+enum Node {
+    Window(usize),
+    Split {
+        split: Split,
+        children: Vec<Node>,
+        /// Rows or columns given to each child, brought back in line with the
+        /// available space every time the layout is arranged.
+        sizes: Vec<usize>,
+    },
+}
+
+/// The outcome of looking for a window while resizing.
+enum Found {
+    Missing,
+    Inside,
+    Resized,
+}
+
+/// How the screen is divided between windows, as a tree of splits with the
+/// windows, known by their id, at the leaves.
+pub struct Layout {
+    root: Node,
+}
+
+impl Layout {
+    pub const fn new(id: usize) -> Self {
+        Self { root: Node::Window(id) }
+    }
+
+    /// Puts window `new` above or to the left of `target`, sharing its space.
+    pub fn split(&mut self, target: usize, new: usize, split: Split) {
+        Self::split_node(&mut self.root, target, new, split);
+    }
+
+    fn split_node(node: &mut Node, target: usize, new: usize, direction: Split) -> bool {
+        match node {
+            Node::Window(id) if *id == target => {
+                *node = Node::Split {
+                    split: direction,
+                    children: vec![Node::Window(new), Node::Window(target)],
+                    sizes: vec![1, 1],
+                };
+                true
+            },
+            Node::Window(_) => false,
+            Node::Split { split, children, sizes } => {
+                let index = children.iter().position(|child| matches!(child, Node::Window(id) if *id == target));
+                if let Some(index) = index
+                    && *split == direction {
+                    let half = sizes[index] / 2;
+                    sizes[index] -= half;
+                    children.insert(index, Node::Window(new));
+                    sizes.insert(index, half);
+                    return true;
+                }
+                children.iter_mut().any(|child| Self::split_node(child, target, new, direction))
+            },
+        }
+    }
+
+    /// Takes window `target` out, its space going to the window next to it.
+    pub fn remove(&mut self, target: usize) {
+        Self::remove_node(&mut self.root, target);
+    }
+
+    fn remove_node(node: &mut Node, target: usize) -> bool {
+        let Node::Split { children, sizes, .. } = node else {
+            return false;
+        };
+        let index = children.iter().position(|child| matches!(child, Node::Window(id) if *id == target));
+        let Some(index) = index else {
+            return children.iter_mut().any(|child| Self::remove_node(child, target));
+        };
+        children.remove(index);
+        let size = sizes.remove(index);
+        let neighbour = index.min(sizes.len().saturating_sub(1));
+        if let Some(neighbour_size) = sizes.get_mut(neighbour) {
+            *neighbour_size += size;
+        }
+        if children.len() == 1
+            && let Some(only) = children.pop() {
+            *node = only;
+        }
+        true
+    }
+
+    /// Grows window `target` by `delta` rows or columns, or shrinks it if
+    /// `delta` is negative, at the expense of the window next to it.
+    pub fn resize(&mut self, target: usize, split: Split, delta: isize) {
+        Self::resize_node(&mut self.root, target, split, delta);
+    }
+
+    fn resize_node(node: &mut Node, target: usize, direction: Split, delta: isize) -> Found {
+        match node {
+            Node::Window(id) if *id == target => Found::Inside,
+            Node::Window(_) => Found::Missing,
+            Node::Split { split, children, sizes } => {
+                for (index, child) in children.iter_mut().enumerate() {
+                    match Self::resize_node(child, target, direction, delta) {
+                        Found::Missing => {},
+                        Found::Resized => return Found::Resized,
+                        Found::Inside if *split == direction => {
+                            Self::move_border(sizes, index, delta);
+                            return Found::Resized;
+                        },
+                        Found::Inside => return Found::Inside,
+                    }
+                }
+                Found::Missing
+            },
+        }
+    }
+
+    fn move_border(sizes: &mut [usize], index: usize, delta: isize) {
+        let neighbour = if index + 1 < sizes.len() { index + 1 } else { index.saturating_sub(1) };
+        if neighbour == index {
+            return;
+        }
+        let (grow, shrink) = if delta > 0 { (index, neighbour) } else { (neighbour, index) };
+        let amount = delta.unsigned_abs().min(sizes[shrink].saturating_sub(MIN_SIZE));
+        sizes[grow] += amount;
+        sizes[shrink] -= amount;
+    }
+
+    /// Gives every window in a split the same share of it.
+    pub fn equalize(&mut self) {
+        Self::equalize_node(&mut self.root);
+    }
+
+    fn equalize_node(node: &mut Node) {
+        if let Node::Split { children, sizes, .. } = node {
+            sizes.fill(1);
+            children.iter_mut().for_each(Self::equalize_node);
+        }
+    }
+
+    /// Lays the windows out in `rect`. Returns where each window goes, in
+    /// order from top left to bottom right, and the columns separating
+    /// windows that are side by side.
+    pub fn arrange(&mut self, rect: Rect) -> (Vec<(usize, Rect)>, Vec<Rect>) {
+        let mut windows = Vec::new();
+        let mut separators = Vec::new();
+        Self::arrange_node(&mut self.root, rect, &mut windows, &mut separators);
+        (windows, separators)
+    }
+
+    fn arrange_node(node: &mut Node, rect: Rect, windows: &mut Vec<(usize, Rect)>, separators: &mut Vec<Rect>) {
+        let Node::Split { split, children, sizes } = node else {
+            if let Node::Window(id) = node {
+                windows.push((*id, rect));
+            }
+            return;
+        };
+        let Size { width, height } = rect.size;
+        let (total, gaps) = match split {
+            Split::Horizontal => (height, 0),
+            Split::Vertical => (width, children.len().saturating_sub(1)),
+        };
+        Self::distribute(sizes, total.saturating_sub(gaps));
+        let mut offset = 0;
+        let last = children.len().saturating_sub(1);
+        for (index, (child, size)) in children.iter_mut().zip(sizes.iter()).enumerate() {
+            let child_rect = match split {
+                Split::Horizontal => Rect {
+                    origin: Coords { row: rect.origin.row + offset, col: rect.origin.col },
+                    size: Size { width, height: *size },
+                },
+                Split::Vertical => Rect {
+                    origin: Coords { row: rect.origin.row, col: rect.origin.col + offset },
+                    size: Size { width: *size, height },
+                },
+            };
+            Self::arrange_node(child, child_rect, windows, separators);
+            offset += size;
+            if *split == Split::Vertical && index < last {
+                separators.push(Rect {
+                    origin: Coords { row: rect.origin.row, col: rect.origin.col + offset },
+                    size: Size { width: 1, height },
+                });
+                offset += 1;
+            }
+        }
+    }
+
+    /// Scales `sizes` so they add up to `available`, keeping their
+    /// proportions and giving every entry at least one cell where possible.
+    fn distribute(sizes: &mut [usize], available: usize) {
+        let total: usize = sizes.iter().sum();
+        if total == available || sizes.is_empty() {
+            return;
+        }
+        let count = sizes.len();
+        for size in sizes.iter_mut() {
+            *size = (*size * available).checked_div(total).unwrap_or(available / count);
+        }
+        let assigned: usize = sizes.iter().sum();
+        if let Some(last) = sizes.last_mut() {
+            *last += available - assigned;
+        }
+        while let Some(empty) = sizes.iter().position(|size| *size == 0) {
+            let Some(largest) = (0..count).max_by_key(|index| sizes[*index]).filter(|index| sizes[*index] > 1) else {
+                break;
+            };
+            sizes[largest] -= 1;
+            sizes[empty] += 1;
+        }
+    }
+}
+
+/// The window next to `from` in `direction`, preferring the one level with
+/// the caret at `caret` when several are.
+pub fn neighbour(windows: &[(usize, Rect)], from: usize, direction: Direction, caret: Coords) -> Option<usize> {
+    let (_, current) = windows.iter().find(|(id, _)| *id == from)?;
+    let top = current.origin.row;
+    let bottom = top + current.size.height;
+    let left = current.origin.col;
+    let right = left + current.size.width;
+    windows
+        .iter()
+        .filter(|(id, _)| *id != from)
+        .filter_map(|(id, rect)| {
+            let (rect_top, rect_left) = (rect.origin.row, rect.origin.col);
+            let (rect_bottom, rect_right) = (rect_top + rect.size.height, rect_left + rect.size.width);
+            let overlaps_rows = rect_top < bottom && top < rect_bottom;
+            let overlaps_cols = rect_left < right && left < rect_right;
+            let (adjacent, along, start, end) = match direction {
+                Direction::Up => (rect_bottom == top && overlaps_cols, caret.col, rect_left, rect_right),
+                Direction::Down => (rect_top == bottom && overlaps_cols, caret.col, rect_left, rect_right),
+                Direction::Left => (rect_right + 1 == left && overlaps_rows, caret.row, rect_top, rect_bottom),
+                Direction::Right => (right + 1 == rect_left && overlaps_rows, caret.row, rect_top, rect_bottom),
+            };
+            let distance = if along < start { start - along } else { along.saturating_sub(end.saturating_sub(1)) };
+            adjacent.then_some((distance, *id))
+        })
+        .min()
+        .map(|(_, id)| id)
+}
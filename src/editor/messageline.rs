@@ -0,0 +1,112 @@
+use std::time::{Duration, Instant};
+use unicode_width::UnicodeWidthStr;
+use super::{
+    terminal::{Coords, Style, StyledLine, Terminal},
+    theme::Group,
+};
+
+/// How long a message stays on screen.
+const MESSAGE_TIMEOUT: Duration = Duration::from_secs(4);
+/// How many messages `:messages` remembers.
+const HISTORY_SIZE: usize = 100;
+
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum Severity {
+    Info,
+    Warning,
+    Error,
+}
+
+impl Severity {
+    fn style(self) -> Style {
+        match self {
+            Self::Info => Style::default(),
+            Self::Warning => Group::WarningMsg.style(),
+            Self::Error => Group::ErrorMsg.style(),
+        }
+    }
+}
+
+struct Message {
+    severity: Severity,
+    text: String,
+    shown_at: Instant,
+}
+
+/// The bottom row of the screen, below the status lines. It shows the
+/// command line while one is typed, and otherwise the last message until
+/// it times out.
+pub struct MessageLine {
+    message: Option<Message>,
+    command_line: Option<String>,
+    history: Vec<String>,
+    needs_redraw: bool,
+    width: usize,
+    row: usize,
+}
+
+impl MessageLine {
+    pub const fn new(row: usize, width: usize) -> Self {
+        Self {
+            message: None,
+            command_line: None,
+            history: Vec::new(),
+            needs_redraw: true,
+            width,
+            row,
+        }
+    }
+
+    pub const fn resize(&mut self, row: usize, width: usize) {
+        self.row = row;
+        self.width = width;
+        self.needs_redraw = true;
+    }
+
+    pub fn show(&mut self, severity: Severity, text: String) {
+        if self.history.len() == HISTORY_SIZE {
+            self.history.remove(0);
+        }
+        self.history.push(text.clone());
+        self.message = Some(Message { severity, text, shown_at: Instant::now() });
+        self.needs_redraw = true;
+    }
+
+    /// The messages shown so far, oldest first.
+    pub fn history(&self) -> &[String] {
+        &self.history
+    }
+
+    /// Shows `command_line` while it is `Some`. Typing one does away with
+    /// the last message.
+    pub fn set_command_line(&mut self, command_line: Option<String>) {
+        if command_line != self.command_line {
+            if command_line.is_some() {
+                self.message = None;
+            }
+            self.command_line = command_line;
+            self.needs_redraw = true;
+        }
+    }
+
+    /// Where the caret goes while a command line is typed.
+    pub fn caret_position(&self) -> Option<Coords> {
+        let command_line = self.command_line.as_ref()?;
+        Some(Coords { row: self.row, col: command_line.width().min(self.width.saturating_sub(1)) })
+    }
+
+    pub fn render(&mut self) {
+        if self.message.as_ref().is_some_and(|message| message.shown_at.elapsed() >= MESSAGE_TIMEOUT) {
+            self.message = None;
+            self.needs_redraw = true;
+        }
+        if !self.needs_redraw {return;}
+        let line = match (&self.command_line, &self.message) {
+            (Some(command_line), _) => StyledLine::plain(command_line),
+            (None, Some(message)) => StyledLine::new(&message.text, message.severity.style()),
+            (None, None) => StyledLine::default(),
+        };
+        Terminal::print_line(Coords { row: self.row, col: 0 }, self.width, &line);
+        self.needs_redraw = false;
+    }
+}
@@ -0,0 +1,94 @@
+use std::fmt::Write;
+use crossterm::event::{Event, KeyCode, KeyEvent, KeyModifiers};
+use super::terminal::{HeadlessBackend, Size, Terminal};
+use super::Editor;
+
+/// Turns a script in key notation into key events. Characters stand for
+/// themselves, and special keys are written in angle brackets, as in
+/// `ihello<Esc>:wq<CR>`: `<Esc>`, `<CR>` or `<Enter>`, `<Tab>`, `<BS>`,
+/// `<Del>`, `<Up>`, `<Down>`, `<Left>`, `<Right>`, `<Space>`, `<lt>` for a
+/// `<`, and `<C-x>` for Ctrl with a key. A `<` that does not start one of
+/// these is just a `<`. Line breaks are left out, so that long scripts can
+/// be spread over several lines.
+#[must_use]
+pub fn parse_keys(script: &str) -> Vec<Event> {
+    let mut events = Vec::new();
+    let mut rest = script;
+    while let Some(c) = rest.chars().next() {
+        let special = rest
+            .strip_prefix('<')
+            .and_then(|inner| inner.split_once('>'))
+            .and_then(|(name, after)| Some((special_key(name)?, after)));
+        if let Some(((code, modifiers), after)) = special {
+            events.push(Event::Key(KeyEvent::new(code, modifiers)));
+            rest = after;
+            continue;
+        }
+        if c != '\n' && c != '\r' {
+            events.push(Event::Key(KeyEvent::new(KeyCode::Char(c), KeyModifiers::NONE)));
+        }
+        rest = &rest[c.len_utf8()..];
+    }
+    events
+}
+
+fn special_key(name: &str) -> Option<(KeyCode, KeyModifiers)> {
+    if let Some(key) = name.strip_prefix("C-").or_else(|| name.strip_prefix("c-")) {
+        let mut chars = key.chars();
+        let c = chars.next().filter(|_| chars.next().is_none())?;
+        return Some((KeyCode::Char(c.to_ascii_lowercase()), KeyModifiers::CONTROL));
+    }
+    let code = match name.to_ascii_lowercase().as_str() {
+        "esc" => KeyCode::Esc,
+        "cr" | "enter" | "return" => KeyCode::Enter,
+        "tab" => KeyCode::Tab,
+        "bs" | "backspace" => KeyCode::Backspace,
+        "del" | "delete" => KeyCode::Delete,
+        "up" => KeyCode::Up,
+        "down" => KeyCode::Down,
+        "left" => KeyCode::Left,
+        "right" => KeyCode::Right,
+        "space" => KeyCode::Char(' '),
+        "lt" => KeyCode::Char('<'),
+        _ => return None,
+    };
+    Some((code, KeyModifiers::NONE))
+}
+
+impl Editor {
+    /// Opens `filenames` on a headless screen of `size`, types `script` as
+    /// `parse_keys` reads it, and returns the buffer shown at the end,
+    /// followed by the screen, the caret and the window title if
+    /// `dump_screen` is set. Keys left after the script quits are not typed.
+    #[must_use]
+    pub fn replay(filenames: &[String], script: &str, size: Size, dump_screen: bool) -> String {
+        let (backend, screen) = HeadlessBackend::new(size);
+        let _backend = Terminal::use_backend(Box::new(backend));
+        let mut editor = Self::open(filenames, size);
+        editor.refresh_screen();
+        for event in parse_keys(script) {
+            if editor.should_quit {
+                break;
+            }
+            editor.evaluate_event(event);
+            editor.refresh_screen();
+        }
+        let mut out = String::new();
+        for line in editor.view().buffer().borrow().text_lines() {
+            let _ = writeln!(out, "{line}");
+        }
+        if dump_screen {
+            let screen = screen.borrow();
+            out.push_str("--- screen ---\n");
+            for row in &screen.rows {
+                let _ = writeln!(out, "{}", row.trim_end());
+            }
+            if let Some(caret) = screen.caret {
+                let shape = screen.caret_shape.name();
+                let _ = writeln!(out, "--- {shape} caret at row {}, column {} ---", caret.row + 1, caret.col + 1);
+            }
+            let _ = writeln!(out, "--- title: {} ---", screen.title);
+        }
+        out
+    }
+}
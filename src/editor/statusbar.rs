@@ -1,15 +1,32 @@
+use unicode_width::{UnicodeWidthChar, UnicodeWidthStr};
 use super::{
-    terminal::{Size,Terminal},
+    terminal::{Coords, StyledLine, Terminal},
+    theme::Group,
     DocumentStatus,
 };
 
+/// The row below each window describing its buffer, drawn in the format of
+/// the `statusline` option. In it, `%` starts one of these items:
+///
+/// - `%M` the mode, or the command line being typed
+/// - `%f` the file name, `%m` `[+]` if modified, `%r` `[RO]` if read-only
+/// - `%y` the filetype, `%e` the encoding, `%n` the line endings
+/// - `%l` the line, `%L` the number of lines, `%p` how far through the file
+///   the line is in percent
+/// - `%c` the column in graphemes, `%v` the column on screen
+/// - `%k` the count and keys of a command being typed
+/// - `%R` the register a macro is being recorded into
+/// - `%=` spreads what is left and right of it apart, `%<` is where the line
+///   is cut if it is too long, and `%%` is a `%`
 pub struct StatusBar {
     current_status: DocumentStatus,
     needs_redraw: bool,
     width: usize,
-    position_y: usize,
+    position: Coords,
 }
 
+/// Marks where cut text was left out.
+const CUT_MARKER: &str = "<";
 
 impl StatusBar {
     pub fn new() -> Self {
@@ -18,14 +35,14 @@ impl StatusBar {
             current_status: DocumentStatus::default(),
             needs_redraw: true,
             width: size.width,
-            position_y: size.height - 1,
+            position: Coords { row: size.height.saturating_sub(1), col: 0 },
         }
     }
 
-    pub fn resize(&mut self, size: Size) {
-        self.width = size.width;
-        self.position_y = size.height - 1;
-        self.needs_redraw = true
+    pub fn resize(&mut self, position: Coords, width: usize) {
+        self.width = width;
+        self.position = position;
+        self.needs_redraw = true;
     }
 
     pub fn update_status(&mut self, status: DocumentStatus) {
@@ -37,19 +54,101 @@ impl StatusBar {
 
     pub fn render(&mut self) {
         if !self.needs_redraw {return;}
-        let line_idx = self.current_status.current_line_index + 1;
-        let total_lines = self.current_status.total_lines + 1;
-        let width = self.width / 3;
-        let lines_info = format!("{line_idx}/{total_lines}");
-        let mode = &self.current_status.bmode_string;
-        let name = &self.current_status.filename;
-        let fileinfo =  if self.current_status.is_modified {
-             name.to_owned()+ "*"
-        } else {
-             name.to_string()
-        };
-        let status = format!("{:<width$}{:^width$}{:>width$}",mode,fileinfo,lines_info);
-        let _ = Terminal::print_row(self.position_y, &status);
+        let (parts, cut) = self.expand();
+        let status = Self::arrange(&parts, cut, self.width);
+        Terminal::print_line(self.position, self.width, &StyledLine::new(&status, Group::StatusLine.style()));
         self.needs_redraw = false;
     }
+
+    /// The format with its items filled in, as the parts between `%=`, and
+    /// the part and byte offset of the `%<`, if any.
+    fn expand(&self) -> (Vec<String>, Option<(usize, usize)>) {
+        let status = &self.current_status;
+        let mut parts = Vec::new();
+        let mut part = String::new();
+        let mut cut = None;
+        let mut chars = status.format.chars();
+        while let Some(c) = chars.next() {
+            if c != '%' {
+                part.push(c);
+                continue;
+            }
+            let line = status.current_line_index + 1;
+            match chars.next() {
+                Some('M') => part.push_str(&status.bmode_string),
+                Some('f') => part.push_str(&status.filename),
+                Some('m') if status.is_modified => part.push_str("[+]"),
+                Some('r') if status.is_read_only => part.push_str("[RO]"),
+                Some('y') => part.push_str(status.filetype),
+                Some('e') => part.push_str(status.encoding),
+                Some('n') => part.push_str(status.line_ending),
+                Some('l') => part.push_str(&line.to_string()),
+                Some('L') => {
+                    // An empty buffer still has the line the caret is on.
+                    part.push_str(&status.total_lines.max(1).to_string());
+                    if status.is_indexing {
+                        part.push('…');
+                    }
+                },
+                Some('p') => {
+                    let percent = line.min(status.total_lines) * 100 / status.total_lines.max(1);
+                    part.push_str(&percent.to_string());
+                },
+                Some('c') => part.push_str(&(status.grapheme_column + 1).to_string()),
+                Some('v') => part.push_str(&(status.display_column + 1).to_string()),
+                Some('k') => part.push_str(&status.pending_keys),
+                Some('R') => {
+                    if let Some(register) = status.recording {
+                        part.push_str("recording @");
+                        part.push(register);
+                    }
+                },
+                Some('=') => parts.push(std::mem::take(&mut part)),
+                Some('<') => cut = Some((parts.len(), part.len())),
+                Some('m' | 'r') => {},
+                Some(other) => part.push(other),
+                None => part.push('%'),
+            }
+        }
+        parts.push(part);
+        (parts, cut)
+    }
+
+    /// Lays the parts out over `width` columns, sharing out the room left
+    /// between them. If they do not fit, text is left out from the `%<` on,
+    /// or from the start, and whatever still does not fit is cut at the end.
+    fn arrange(parts: &[String], cut: Option<(usize, usize)>, width: usize) -> String {
+        let used: usize = parts.iter().map(|part| part.width()).sum();
+        if used <= width {
+            let gaps = parts.len().saturating_sub(1).max(1);
+            let room = width - used;
+            let mut line = String::new();
+            for (index, part) in parts.iter().enumerate() {
+                line.push_str(part);
+                if index < gaps {
+                    let gap = room / gaps + usize::from(index < room % gaps);
+                    line.push_str(&" ".repeat(gap));
+                }
+            }
+            return line;
+        }
+        let offset = cut.map_or(0, |(part, offset)| parts[..part].iter().map(String::len).sum::<usize>() + offset);
+        let text = parts.concat();
+        let (head, tail) = text.split_at(offset);
+        let mut excess = used + CUT_MARKER.width() - width;
+        let tail = tail.trim_start_matches(|c: char| {
+            let drop = excess > 0;
+            excess = excess.saturating_sub(c.width().unwrap_or(0));
+            drop
+        });
+        let mut room = width;
+        format!("{head}{CUT_MARKER}{tail}")
+            .chars()
+            .take_while(|c| {
+                let fits = c.width().unwrap_or(0) <= room;
+                room = room.saturating_sub(c.width().unwrap_or(0));
+                fits
+            })
+            .collect()
+    }
 }
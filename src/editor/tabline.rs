@@ -0,0 +1,68 @@
+use unicode_width::UnicodeWidthChar;
+use super::terminal::{Coords, StyledLine, Terminal};
+use super::theme::Group;
+
+/// The top row listing the tab pages, shown while there is more than one.
+/// The current tab page is highlighted.
+pub struct TabLine {
+    labels: Vec<String>,
+    current: usize,
+    needs_redraw: bool,
+    width: usize,
+}
+
+impl TabLine {
+    pub const fn new(width: usize) -> Self {
+        Self {
+            labels: Vec::new(),
+            current: 0,
+            needs_redraw: true,
+            width,
+        }
+    }
+
+    pub const fn resize(&mut self, width: usize) {
+        self.width = width;
+        self.needs_redraw = true;
+    }
+
+    pub fn update(&mut self, labels: Vec<String>, current: usize) {
+        if labels != self.labels || current != self.current {
+            self.labels = labels;
+            self.current = current;
+            self.needs_redraw = true;
+        }
+    }
+
+    pub fn render(&mut self) {
+        if !self.needs_redraw {return;}
+        let tab = |(index, label): (usize, &String)| format!(" {} {label} ", index + 1);
+        let before: String = self.labels.iter().enumerate().take(self.current).map(tab).collect();
+        let current: String = self.labels.iter().enumerate().skip(self.current).take(1).map(tab).collect();
+        let after: String = self.labels.iter().enumerate().skip(self.current + 1).map(tab).collect();
+        let before = Self::fit(&before, self.width);
+        let current = Self::fit(&current, self.width.saturating_sub(before.1));
+        let after = Self::fit(&after, self.width.saturating_sub(before.1 + current.1));
+        let mut line = StyledLine::default();
+        line.push(&before.0, Group::TabLine.style());
+        line.push(&current.0, Group::TabLineSel.style());
+        line.push(&after.0, Group::TabLine.style());
+        Terminal::print_line(Coords::default(), self.width, &line);
+        self.needs_redraw = false;
+    }
+
+    /// Cuts `text` down to at most `width` columns, returning what is left
+    /// and how wide it is.
+    fn fit(text: &str, width: usize) -> (String, usize) {
+        let mut used = 0;
+        let fitted = text
+            .chars()
+            .take_while(|c| {
+                used += c.width().unwrap_or(0);
+                used <= width
+            })
+            .collect::<String>();
+        let used = fitted.chars().map(|c| c.width().unwrap_or(0)).sum();
+        (fitted, used)
+    }
+}
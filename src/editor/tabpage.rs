@@ -0,0 +1,194 @@
+use super::{
+    editorcommand::{Direction, EditorCommand},
+    layout::{self, Layout, Rect, Split, MIN_SIZE},
+    terminal::{Coords, Size, StyledLine, Terminal},
+    theme::Group,
+    view::View,
+    window::Window,
+};
+
+/// A set of windows with their own layout. Only the current tab page is on
+/// screen.
+pub struct TabPage {
+    /// In the order the layout places them, top left to bottom right.
+    windows: Vec<Window>,
+    /// Index into `windows` of the window keys go to.
+    active: usize,
+    layout: Layout,
+    next_window_id: usize,
+    rect: Rect,
+    needs_layout: bool,
+}
+
+impl TabPage {
+    pub fn new(view: View) -> Self {
+        Self {
+            windows: vec![Window::new(0, view)],
+            active: 0,
+            layout: Layout::new(0),
+            next_window_id: 1,
+            rect: Rect::default(),
+            needs_layout: true,
+        }
+    }
+
+    pub fn view(&self) -> &View {
+        &self.windows[self.active].view
+    }
+
+    pub fn view_mut(&mut self) -> &mut View {
+        &mut self.windows[self.active].view
+    }
+
+    pub fn windows_mut(&mut self) -> impl Iterator<Item = &mut Window> {
+        self.windows.iter_mut()
+    }
+
+    pub fn window_count(&self) -> usize {
+        self.windows.len()
+    }
+
+    /// What the tab line shows for this tab page: the file in the current
+    /// window, marked with `+` if a buffer shown in the tab page is modified.
+    pub fn label(&self) -> String {
+        let filename = self.view().buffer().borrow().filename().to_string();
+        let modified = self.windows.iter().any(|window| window.view.buffer().borrow().is_dirty());
+        if modified { format!("{filename} +") } else { filename }
+    }
+
+    /// Gives the tab page `rect` of the screen. Everything is laid out and
+    /// drawn again on the next render, as when coming back to the tab page.
+    pub fn place(&mut self, rect: Rect) {
+        self.rect = rect;
+        self.needs_layout = true;
+    }
+
+    /// Splits the current window in two, the new window showing the same
+    /// buffer. Returns `false` if there is no room for it.
+    pub fn split(&mut self, split: Split) -> bool {
+        let Size { width, height } = self.windows[self.active].rect().size;
+        let room = match split {
+            Split::Horizontal => height >= 2 * MIN_SIZE,
+            Split::Vertical => width > 2 * MIN_SIZE,
+        };
+        if !room {
+            return false;
+        }
+        let id = self.next_window_id;
+        self.next_window_id += 1;
+        let view = self.view().split_off();
+        self.layout.split(self.windows[self.active].id, id, split);
+        self.windows.insert(self.active, Window::new(id, view));
+        self.needs_layout = true;
+        true
+    }
+
+    pub fn focus(&mut self, direction: Direction) {
+        let placements: Vec<(usize, Rect)> = self.windows.iter().map(|window| (window.id, window.rect())).collect();
+        let caret = self.view().caret_position();
+        let target = layout::neighbour(&placements, self.windows[self.active].id, direction, caret);
+        if let Some(index) = target.and_then(|id| self.windows.iter().position(|window| window.id == id)) {
+            self.active = index;
+        }
+    }
+
+    /// Hands a mouse command to the window it is meant for. A click goes to
+    /// the window under the pointer and makes it the current one, a drag
+    /// stays with the window the click was in, and the wheel scrolls the
+    /// window under the pointer.
+    pub fn handle_mouse(&mut self, command: EditorCommand) {
+        let index = match command {
+            EditorCommand::Click(at) => {
+                let Some(index) = self.window_at(at) else {
+                    return;
+                };
+                self.active = index;
+                index
+            },
+            EditorCommand::Scroll(_, at) => match self.window_at(at) {
+                Some(index) => index,
+                None => return,
+            },
+            _ => self.active,
+        };
+        self.windows[index].view.handle_mouse(command);
+    }
+
+    /// The index of the window covering the cell `at`, status line included.
+    fn window_at(&self, at: Coords) -> Option<usize> {
+        self.windows.iter().position(|window| {
+            let Rect { origin, size } = window.rect();
+            (origin.row..origin.row + size.height).contains(&at.row) && (origin.col..origin.col + size.width).contains(&at.col)
+        })
+    }
+
+    /// Moves to the window `step` places further on, wrapping around.
+    pub fn cycle(&mut self, step: usize) {
+        self.active = (self.active + step) % self.windows.len();
+    }
+
+    /// Closes the current window, unless it is the only one.
+    pub fn close_window(&mut self) {
+        if self.windows.len() == 1 {
+            return;
+        }
+        let window = self.windows.remove(self.active);
+        self.layout.remove(window.id);
+        self.active = self.active.min(self.windows.len() - 1);
+        self.needs_layout = true;
+    }
+
+    pub fn only_window(&mut self) {
+        let window = self.windows.swap_remove(self.active);
+        self.layout = Layout::new(window.id);
+        self.windows = vec![window];
+        self.active = 0;
+        self.needs_layout = true;
+    }
+
+    pub fn grow_window(&mut self, split: Split, delta: isize) {
+        self.layout.resize(self.windows[self.active].id, split, delta);
+        self.needs_layout = true;
+    }
+
+    pub fn equalize(&mut self) {
+        self.layout.equalize();
+        self.needs_layout = true;
+    }
+
+    pub fn window_height(&self) -> usize {
+        self.windows[self.active].rect().size.height
+    }
+
+    pub fn render(&mut self) {
+        if self.needs_layout {
+            self.relayout();
+        }
+        for window in &mut self.windows {
+            window.render();
+        }
+    }
+
+    pub fn caret_position(&self) -> Coords {
+        self.view().caret_position()
+    }
+
+    fn relayout(&mut self) {
+        let (placements, separators) = self.layout.arrange(self.rect);
+        let active_id = self.windows[self.active].id;
+        for (id, rect) in &placements {
+            if let Some(window) = self.windows.iter_mut().find(|window| window.id == *id) {
+                window.set_rect(*rect);
+            }
+        }
+        self.windows.sort_by_key(|window| placements.iter().position(|(id, _)| *id == window.id));
+        self.active = self.windows.iter().position(|window| window.id == active_id).unwrap_or_default();
+        for separator in separators {
+            for row in 0..separator.size.height {
+                let at = Coords { row: separator.origin.row + row, col: separator.origin.col };
+                Terminal::print_line(at, 1, &StyledLine::new("│", Group::VertSplit.style()));
+            }
+        }
+        self.needs_layout = false;
+    }
+}
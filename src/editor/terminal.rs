@@ -1,25 +1,182 @@
-use std::io::{stdout, Error, Write};
-use crossterm::cursor;
-use crossterm::queue;
-use crossterm::terminal::{disable_raw_mode, enable_raw_mode, Clear, ClearType, EnterAlternateScreen, LeaveAlternateScreen};
-use crossterm::terminal;
-use crossterm::style;
+use std::cell::RefCell;
+use std::io::Error;
+use std::ops::Range;
+use unicode_segmentation::UnicodeSegmentation;
+use unicode_width::UnicodeWidthStr;
 
+mod backend;
+mod color;
+mod screen;
+use backend::{Backend, CrosstermBackend};
+pub(super) use screen::Screen;
+pub use backend::HeadlessBackend;
+#[cfg(test)]
+pub use backend::Snapshot;
+#[cfg(test)]
+pub use color::ColorDepth;
+
+pub use crossterm::style::{Attribute, Attributes, Color};
+
+thread_local! {
+    static SCREEN: RefCell<Screen> = RefCell::new(Screen::default());
+    static BACKEND: RefCell<Box<dyn Backend>> = RefCell::new(Box::new(CrosstermBackend::default()));
+}
+
+/// Keeps a backend in place until dropped, then puts back the backend and
+/// the screen it took over from.
+pub struct BackendGuard {
+    previous: Option<(Box<dyn Backend>, Screen)>,
+}
+
+impl Drop for BackendGuard {
+    fn drop(&mut self) {
+        if let Some((backend, screen)) = self.previous.take() {
+            BACKEND.set(backend);
+            SCREEN.set(screen);
+        }
+    }
+}
+
+/// Drawing goes to a copy of the screen kept in memory, and reaches the
+/// backend on `execute`: the terminal, as the changes since the last frame,
+/// or another copy in memory when running headless.
 pub struct Terminal;
 
-#[derive(Copy, Clone, Default)]
+/// How a span of text is drawn: colors, left to the terminal when unset,
+/// and attributes such as bold, italic, underlined and reverse.
+#[derive(Copy, Clone, Default, PartialEq, Eq, Debug)]
+pub struct Style {
+    pub foreground: Option<Color>,
+    pub background: Option<Color>,
+    pub attributes: Attributes,
+}
+
+impl Style {
+    pub const fn foreground(color: Color) -> Self {
+        Self { foreground: Some(color), background: None, attributes: Attributes::none() }
+    }
+
+    pub const fn background(color: Color) -> Self {
+        Self { foreground: None, background: Some(color), attributes: Attributes::none() }
+    }
+
+    pub const fn with(self, attribute: Attribute) -> Self {
+        Self { attributes: self.attributes.with(attribute), ..self }
+    }
+
+    /// This style laid over `below`: its colors where it sets them, and the
+    /// attributes of both.
+    fn over(self, below: Self) -> Self {
+        Self {
+            foreground: self.foreground.or(below.foreground),
+            background: self.background.or(below.background),
+            attributes: below.attributes | self.attributes,
+        }
+    }
+}
+
+struct Layer {
+    columns: Range<usize>,
+    style: Style,
+    priority: u8,
+}
+
+/// A row of text to draw: spans of text in their own styles, with highlight
+/// layers such as syntax colors, search matches and the selection laid over
+/// ranges of its display columns.
+#[derive(Default)]
+pub struct StyledLine {
+    spans: Vec<(String, Style)>,
+    layers: Vec<Layer>,
+}
+
+impl StyledLine {
+    pub fn new(text: &str, style: Style) -> Self {
+        let mut line = Self::default();
+        line.push(text, style);
+        line
+    }
+
+    pub fn plain(text: &str) -> Self {
+        Self::new(text, Style::default())
+    }
+
+    pub fn push(&mut self, text: &str, style: Style) {
+        self.spans.push((text.to_string(), style));
+    }
+
+    /// Lays `style` over the display columns `columns`. Where layers
+    /// overlap, the colors of the one with the higher `priority` win, and
+    /// the attributes of all of them add up.
+    pub fn highlight(&mut self, columns: Range<usize>, style: Style, priority: u8) {
+        if columns.start < columns.end {
+            self.layers.push(Layer { columns, style, priority });
+        }
+    }
+
+    pub fn width(&self) -> usize {
+        self.spans.iter().map(|(text, _)| text.width()).sum()
+    }
+
+    /// The text cut into runs of one style each, the layers merged in. Each
+    /// grapheme takes the style of the column it starts at.
+    pub(super) fn runs(&self) -> Vec<(String, Style)> {
+        let mut layers: Vec<&Layer> = self.layers.iter().collect();
+        layers.sort_by_key(|layer| layer.priority);
+        let mut runs: Vec<(String, Style)> = Vec::new();
+        let mut column = 0;
+        for (text, span_style) in &self.spans {
+            for grapheme in text.graphemes(true) {
+                let style = layers
+                    .iter()
+                    .filter(|layer| layer.columns.contains(&column))
+                    .fold(*span_style, |style, layer| layer.style.over(style));
+                match runs.last_mut() {
+                    Some((run, run_style)) if *run_style == style => run.push_str(grapheme),
+                    _ => runs.push((grapheme.to_string(), style)),
+                }
+                column += grapheme.width();
+            }
+        }
+        runs
+    }
+}
+
+#[derive(Copy, Clone, Default, PartialEq, Eq, Debug)]
 pub struct Size {
     pub width: usize,
     pub height: usize
 }
 
-#[derive(Copy, Clone, Default)]
+/// How the caret is drawn.
+#[derive(Copy, Clone, Default, PartialEq, Eq, Debug)]
+pub enum CaretShape {
+    #[default]
+    Block,
+    /// A thin line before the cell, between two graphemes.
+    Bar,
+    Underline,
+}
+
+impl CaretShape {
+    pub const fn name(self) -> &'static str {
+        match self {
+            Self::Block => "block",
+            Self::Bar => "bar",
+            Self::Underline => "underline",
+        }
+    }
+}
+
+/// A cell of the screen, counted from the top left one.
+#[derive(Copy, Clone, Default, PartialEq, Eq, Debug)]
 pub struct Coords {
     pub row: usize,
     pub col: usize,
 }
 
 impl Coords {
+    #[must_use]
     pub const fn saturating_sub(self, other: Self) -> Self {
         Self {
             row: self.row.saturating_sub(other.row),
@@ -31,93 +188,96 @@ impl Coords {
 impl Terminal {
 
     pub fn initialize() -> Result<(), Error> {
-        enable_raw_mode()?;
-        Self::enter_alternate()?;
-        Self::clear_screen()?;
+        BACKEND.with_borrow_mut(|backend| backend.initialize())?;
+        let depth = BACKEND.with_borrow(|backend| backend.color_depth());
+        SCREEN.with_borrow_mut(|screen| screen.set_color_depth(depth));
+        Self::resize(Self::size()?);
+        Self::clear_screen();
         Self::execute()?;
         Ok(())
     }
 
     pub fn terminate() -> Result<(), Error> {
-        Self::leave_alternate()?;
-        Self::show_caret()?;
-        Self::execute()?;
-        disable_raw_mode()?;
-        Ok(())
+        BACKEND.with_borrow_mut(|backend| backend.terminate())
     }
 
-    pub fn complex_print(row: usize, s1: Option<&str>, s2: Option<&str>, s3: Option<&str>) -> Result<(), Error> {
-        Self::move_caret_to(Coords {row, col:0})?;
-        Self::clear_line()?;
-        Self::print(s1,s2,s3)?;
-        Ok(())
-    }
-    
-    pub fn print_row(row: usize, s: &str) {
-        Self::complex_print(row, Some(s), None, None);
+    /// Sends frames to `backend`, on a fresh screen, rather than to the
+    /// terminal until the returned guard is dropped.
+    #[must_use]
+    pub fn use_backend(backend: Box<dyn Backend>) -> BackendGuard {
+        let previous = (BACKEND.replace(backend), SCREEN.take());
+        BackendGuard { previous: Some(previous) }
     }
 
-    pub fn enter_alternate() -> Result<(), Error>{
-        queue!(stdout(), EnterAlternateScreen)?;
-        Ok(())
+    /// Sizes the screen anew. What the terminal shows after it was resized
+    /// is unknown, so the next frame is drawn in full.
+    pub fn resize(size: Size) {
+        SCREEN.with_borrow_mut(|screen| screen.resize(size));
     }
-    
-    pub fn leave_alternate() -> Result<(), Error>{
-        queue!(stdout(), LeaveAlternateScreen)?;
-        Ok(())
+
+    /// Sets the style everything is drawn over, such as the colors of the
+    /// text and background of a theme.
+    pub fn set_base_style(style: Style) {
+        SCREEN.with_borrow_mut(|screen| screen.set_base(style));
     }
 
-    pub fn clear_screen() -> Result<(), Error> {
-        queue!(stdout(), Clear(ClearType::All))?;
-        Ok(())
+    /// Prints `line` starting at `at`, cut or padded with spaces to `width`
+    /// columns, so that whatever was there before is overwritten without
+    /// touching the rest of the row.
+    pub fn print_line(at: Coords, width: usize, line: &StyledLine) {
+        SCREEN.with_borrow_mut(|screen| {
+            let base = screen.base();
+            let end = at.col + width;
+            let mut col = at.col;
+            for (text, style) in line.runs() {
+                for grapheme in text.graphemes(true) {
+                    if col + grapheme.width() > end {
+                        break;
+                    }
+                    col += screen.put(Coords { row: at.row, col }, grapheme, style.over(base));
+                }
+            }
+            while col < end {
+                col += screen.put(Coords { row: at.row, col }, " ", base);
+            }
+        });
     }
 
-    pub fn clear_line() -> Result<(), Error> {
-        queue!(stdout(), Clear(ClearType::CurrentLine))?;
-        Ok(())
+    pub fn clear_screen() {
+        SCREEN.with_borrow_mut(Screen::invalidate);
     }
 
-    pub fn move_caret_to(coords: Coords) -> Result<(), Error> {
-        #[allow(clippy::as_conversions, clippy::cast_possible_truncation)]
-        queue!(stdout(), cursor::MoveTo(coords.col as u16, coords.row as u16))?;
-        Ok(())
+    pub fn move_caret_to(coords: Coords) {
+        SCREEN.with_borrow_mut(|screen| screen.move_caret_to(coords));
     }
 
     pub fn size() -> Result<Size, Error> {
-        let (width_u16, height_u16) = terminal::size()?;
-        #[allow(clippy::as_conversions)]
-        let height = height_u16 as usize;
-        #[allow(clippy::as_conversions)]
-        let width = width_u16 as usize;
-        Ok(Size { width, height} )
+        BACKEND.with_borrow(|backend| backend.size())
     }
 
-    pub fn hide_caret() -> Result<(), Error> {
-        queue!(stdout(), cursor::Hide)?;
-        Ok(())
+    pub fn set_caret_shape(shape: CaretShape) {
+        SCREEN.with_borrow_mut(|screen| screen.set_caret_shape(shape));
     }
 
-    pub fn show_caret() -> Result<(), Error> {
-        queue!(stdout(), cursor::Show)?;
-        Ok(())
+    /// Sets the title of the window the terminal is in. The title it had
+    /// before is put back on `terminate`.
+    pub fn set_title(title: &str) {
+        SCREEN.with_borrow_mut(|screen| screen.set_title(title));
     }
 
-    pub fn print(s1: Option<&str>, s2: Option<&str>, s3: Option<&str>) -> Result<(), Error> {
-        if let Some(s) = s1 {queue!(stdout(), style::Print(s))?;}
-        if let Some(s) = s2 {queue!(
-            stdout(),
-            style::SetBackgroundColor(style::Color::DarkBlue),
-            style::Print(s),
-            style::ResetColor
-        )?;}
-        if let Some(s) = s3 {
-        queue!(stdout(), style::Print(s))?;
-        }
-        Ok(())
+    pub fn hide_caret() {
+        SCREEN.with_borrow_mut(|screen| screen.set_caret_visible(false));
+    }
+
+    pub fn show_caret() {
+        SCREEN.with_borrow_mut(|screen| screen.set_caret_visible(true));
     }
-    
+
+    pub fn print(s: &str) -> Result<(), Error> {
+        BACKEND.with_borrow_mut(|backend| backend.print(s))
+    }
+
     pub fn execute() -> Result<(), Error> {
-        stdout().flush()?;
-        Ok(())
+        BACKEND.with_borrow_mut(|backend| SCREEN.with_borrow_mut(|screen| backend.present(screen)))
     }
 }
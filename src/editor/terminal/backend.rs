@@ -0,0 +1,150 @@
+use std::cell::RefCell;
+use std::io::{stdout, Error, Write};
+use std::rc::Rc;
+use crossterm::{cursor, queue, style, terminal};
+use crossterm::event::{
+    DisableBracketedPaste, DisableFocusChange, DisableMouseCapture, EnableBracketedPaste, EnableFocusChange, EnableMouseCapture,
+};
+use crossterm::terminal::{disable_raw_mode, enable_raw_mode, EnterAlternateScreen, LeaveAlternateScreen};
+use super::color::ColorDepth;
+use super::screen::Screen;
+use super::{CaretShape, Coords, Size};
+
+/// Saves the window title on the title stack of the terminal, where xterm
+/// and most terminals after it keep one, to be put back with `POP_TITLE`.
+const PUSH_TITLE: &str = "\x1b[22;0t";
+const POP_TITLE: &str = "\x1b[23;0t";
+
+/// Where the frames drawn on the `Screen` end up.
+pub trait Backend {
+    fn initialize(&mut self) -> Result<(), Error>;
+    /// Puts things back the way `initialize` found them.
+    fn terminate(&mut self) -> Result<(), Error>;
+    fn size(&self) -> Result<Size, Error>;
+    fn color_depth(&self) -> ColorDepth;
+    /// Shows the next frame of `screen`.
+    fn present(&mut self, screen: &mut Screen) -> Result<(), Error>;
+    /// Prints `text` outside of any frame, as when saying goodbye.
+    fn print(&mut self, text: &str) -> Result<(), Error>;
+}
+
+/// The terminal the editor runs in, in raw mode on the alternate screen.
+#[derive(Default)]
+pub struct CrosstermBackend {
+    /// Whether the terminal was set up, and so has to be restored.
+    initialized: bool,
+}
+
+impl Backend for CrosstermBackend {
+    fn initialize(&mut self) -> Result<(), Error> {
+        enable_raw_mode()?;
+        self.initialized = true;
+        queue!(
+            stdout(),
+            style::Print(PUSH_TITLE),
+            EnterAlternateScreen,
+            EnableFocusChange,
+            EnableMouseCapture,
+            EnableBracketedPaste,
+        )?;
+        Ok(())
+    }
+
+    fn terminate(&mut self) -> Result<(), Error> {
+        if !std::mem::take(&mut self.initialized) {
+            return Ok(());
+        }
+        queue!(
+            stdout(),
+            DisableBracketedPaste,
+            DisableMouseCapture,
+            DisableFocusChange,
+            LeaveAlternateScreen,
+            cursor::SetCursorStyle::DefaultUserShape,
+            cursor::Show,
+            style::Print(POP_TITLE),
+        )?;
+        stdout().flush()?;
+        disable_raw_mode()?;
+        Ok(())
+    }
+
+    fn size(&self) -> Result<Size, Error> {
+        let (width, height) = terminal::size()?;
+        Ok(Size { width: usize::from(width), height: usize::from(height) })
+    }
+
+    fn color_depth(&self) -> ColorDepth {
+        ColorDepth::detect()
+    }
+
+    fn present(&mut self, screen: &mut Screen) -> Result<(), Error> {
+        let frame = screen.flush()?;
+        stdout().write_all(&frame)?;
+        stdout().flush()?;
+        Ok(())
+    }
+
+    fn print(&mut self, text: &str) -> Result<(), Error> {
+        queue!(stdout(), style::Print(text))?;
+        Ok(())
+    }
+}
+
+/// What a `HeadlessBackend` was last given to show.
+#[derive(Default)]
+pub struct Snapshot {
+    pub rows: Vec<String>,
+    /// Where the caret is, if it is shown.
+    pub caret: Option<Coords>,
+    pub caret_shape: CaretShape,
+    pub title: String,
+}
+
+/// A screen of a fixed size that only exists in memory, to run the editor
+/// without a terminal. Every frame it is given replaces a `Snapshot`
+/// shared with whoever looks at it.
+pub struct HeadlessBackend {
+    size: Size,
+    snapshot: Rc<RefCell<Snapshot>>,
+}
+
+impl HeadlessBackend {
+    pub fn new(size: Size) -> (Self, Rc<RefCell<Snapshot>>) {
+        let snapshot = Rc::default();
+        (Self { size, snapshot: Rc::clone(&snapshot) }, snapshot)
+    }
+}
+
+impl Backend for HeadlessBackend {
+    fn initialize(&mut self) -> Result<(), Error> {
+        Ok(())
+    }
+
+    fn terminate(&mut self) -> Result<(), Error> {
+        Ok(())
+    }
+
+    fn size(&self) -> Result<Size, Error> {
+        Ok(self.size)
+    }
+
+    fn color_depth(&self) -> ColorDepth {
+        ColorDepth::TrueColor
+    }
+
+    fn present(&mut self, screen: &mut Screen) -> Result<(), Error> {
+        screen.flush()?;
+        *self.snapshot.borrow_mut() = Snapshot {
+            rows: screen.rows(),
+            caret: screen.caret(),
+            caret_shape: screen.caret_shape(),
+            title: screen.title().to_string(),
+        };
+        Ok(())
+    }
+
+    fn print(&mut self, _text: &str) -> Result<(), Error> {
+        Ok(())
+    }
+}
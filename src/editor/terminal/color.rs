@@ -0,0 +1,106 @@
+use super::Color;
+
+/// How many colors the terminal can show.
+#[derive(Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Default, Debug)]
+pub enum ColorDepth {
+    Ansi16,
+    #[default]
+    Ansi256,
+    TrueColor,
+}
+
+/// The sixteen basic colors with the RGB values terminals commonly give
+/// them, to find the closest one to a color they cannot show.
+const ANSI_16: [(Color, (u8, u8, u8)); 16] = [
+    (Color::Black, (0, 0, 0)),
+    (Color::DarkRed, (128, 0, 0)),
+    (Color::DarkGreen, (0, 128, 0)),
+    (Color::DarkYellow, (128, 128, 0)),
+    (Color::DarkBlue, (0, 0, 128)),
+    (Color::DarkMagenta, (128, 0, 128)),
+    (Color::DarkCyan, (0, 128, 128)),
+    (Color::Grey, (192, 192, 192)),
+    (Color::DarkGrey, (128, 128, 128)),
+    (Color::Red, (255, 0, 0)),
+    (Color::Green, (0, 255, 0)),
+    (Color::Yellow, (255, 255, 0)),
+    (Color::Blue, (0, 0, 255)),
+    (Color::Magenta, (255, 0, 255)),
+    (Color::Cyan, (0, 255, 255)),
+    (Color::White, (255, 255, 255)),
+];
+
+/// The levels of each channel in the 6×6×6 color cube of the 256-color
+/// palette.
+const CUBE_LEVELS: [u8; 6] = [0, 95, 135, 175, 215, 255];
+
+impl ColorDepth {
+    /// Tells the depth from `COLORTERM`, which terminals with 24-bit color
+    /// set to `truecolor` or `24bit`, and `TERM`.
+    pub fn detect() -> Self {
+        let colorterm = std::env::var("COLORTERM").unwrap_or_default();
+        let term = std::env::var("TERM").unwrap_or_default();
+        if colorterm == "truecolor" || colorterm == "24bit" {
+            Self::TrueColor
+        } else if term.contains("256color") || !colorterm.is_empty() {
+            Self::Ansi256
+        } else {
+            Self::Ansi16
+        }
+    }
+
+    /// `color`, or the closest color to it the terminal can show.
+    pub fn fit(self, color: Color) -> Color {
+        match (self, color) {
+            (Self::TrueColor, _) | (_, Color::AnsiValue(0..16)) => color,
+            (Self::Ansi256, Color::Rgb { r, g, b }) => Color::AnsiValue(to_256(r, g, b)),
+            (Self::Ansi16, Color::Rgb { r, g, b }) => to_16(r, g, b),
+            (Self::Ansi16, Color::AnsiValue(value)) => {
+                let (r, g, b) = from_256(value);
+                to_16(r, g, b)
+            },
+            _ => color,
+        }
+    }
+}
+
+fn distance((r1, g1, b1): (u8, u8, u8), (r2, g2, b2): (u8, u8, u8)) -> u32 {
+    let square = |a: u8, b: u8| u32::from(a.abs_diff(b)).pow(2);
+    square(r1, r2) + square(g1, g2) + square(b1, b2)
+}
+
+fn to_16(r: u8, g: u8, b: u8) -> Color {
+    ANSI_16
+        .iter()
+        .min_by_key(|(_, rgb)| distance(*rgb, (r, g, b)))
+        .map_or(Color::Reset, |(color, _)| *color)
+}
+
+/// The closest entry of the color cube or the grayscale ramp.
+fn to_256(r: u8, g: u8, b: u8) -> u8 {
+    let level = |channel: u8| {
+        let closest = CUBE_LEVELS.iter().enumerate().min_by_key(|(_, level)| level.abs_diff(channel));
+        closest.map_or(0, |(index, _)| u8::try_from(index).unwrap_or(0))
+    };
+    let (cr, cg, cb) = (level(r), level(g), level(b));
+    let cube = 16 + 36 * cr + 6 * cg + cb;
+    let average = u8::try_from((u16::from(r) + u16::from(g) + u16::from(b)) / 3).unwrap_or(u8::MAX);
+    let gray_step = (average.saturating_sub(8) / 10).min(23);
+    let gray = 232 + gray_step;
+    if distance(from_256(gray), (r, g, b)) < distance(from_256(cube), (r, g, b)) { gray } else { cube }
+}
+
+fn from_256(value: u8) -> (u8, u8, u8) {
+    match value {
+        0..16 => ANSI_16[usize::from(value)].1,
+        16..232 => {
+            let index = value - 16;
+            let level = |step: u8| CUBE_LEVELS[usize::from(step)];
+            (level(index / 36), level(index / 6 % 6), level(index % 6))
+        },
+        _ => {
+            let gray = 8 + (value - 232) * 10;
+            (gray, gray, gray)
+        },
+    }
+}
@@ -0,0 +1,232 @@
+use std::io::Error;
+use crossterm::{cursor, queue, style};
+use crossterm::terminal::{BeginSynchronizedUpdate, Clear, ClearType, EndSynchronizedUpdate, SetTitle};
+use unicode_width::UnicodeWidthStr;
+use super::color::ColorDepth;
+use super::{Attribute, CaretShape, Coords, Size, Style};
+
+#[derive(Clone, PartialEq, Eq)]
+struct Cell {
+    /// Empty for the second column of a wide grapheme.
+    grapheme: String,
+    style: Style,
+}
+
+impl Cell {
+    fn blank(style: Style) -> Self {
+        Self { grapheme: " ".to_string(), style }
+    }
+
+    const fn is_continuation(&self) -> bool {
+        self.grapheme.is_empty()
+    }
+}
+
+/// What the terminal shows and what it should show, cell by cell. Drawing
+/// only changes the next frame; `flush` then sends the terminal just the
+/// cells that differ from the frame it was sent last.
+#[derive(Default)]
+pub struct Screen {
+    size: Size,
+    previous: Vec<Cell>,
+    next: Vec<Cell>,
+    /// What the terminal shows is unknown, as after a resize, so it is
+    /// cleared before the next frame.
+    stale: bool,
+    caret: Coords,
+    caret_visible: bool,
+    caret_shape: CaretShape,
+    title: String,
+    /// The caret shape and title the terminal was last sent, if any.
+    sent_caret_shape: Option<CaretShape>,
+    sent_title: Option<String>,
+    /// Colors are sent as the closest ones the terminal can show.
+    depth: ColorDepth,
+    base: Style,
+}
+
+impl Screen {
+    pub fn resize(&mut self, size: Size) {
+        if size == self.size && !self.next.is_empty() {
+            return;
+        }
+        self.size = size;
+        self.next = vec![Cell::blank(Style::default()); size.width * size.height];
+        self.stale = true;
+    }
+
+    pub const fn invalidate(&mut self) {
+        self.stale = true;
+    }
+
+    pub const fn set_color_depth(&mut self, depth: ColorDepth) {
+        self.depth = depth;
+    }
+
+    pub const fn base(&self) -> Style {
+        self.base
+    }
+
+    pub const fn set_base(&mut self, style: Style) {
+        self.base = style;
+    }
+
+    pub const fn move_caret_to(&mut self, coords: Coords) {
+        self.caret = coords;
+    }
+
+    pub const fn set_caret_visible(&mut self, visible: bool) {
+        self.caret_visible = visible;
+    }
+
+    pub const fn caret_shape(&self) -> CaretShape {
+        self.caret_shape
+    }
+
+    pub const fn set_caret_shape(&mut self, shape: CaretShape) {
+        self.caret_shape = shape;
+    }
+
+    pub fn title(&self) -> &str {
+        &self.title
+    }
+
+    pub fn set_title(&mut self, title: &str) {
+        title.clone_into(&mut self.title);
+    }
+
+    fn index(&self, row: usize, col: usize) -> Option<usize> {
+        (row < self.size.height && col < self.size.width).then_some(row * self.size.width + col)
+    }
+
+    /// Puts `grapheme` at `at`, blanking what is left of any wide grapheme
+    /// it overwrites half of. Returns how many columns it took up.
+    pub fn put(&mut self, at: Coords, grapheme: &str, style: Style) -> usize {
+        let width = grapheme.width();
+        if width == 0 {
+            return 0;
+        }
+        let Some(index) = self.index(at.row, at.col) else {
+            return width;
+        };
+        if at.col + width > self.size.width {
+            self.put(at, " ", style);
+            return width;
+        }
+        if self.next[index].is_continuation() && at.col > 0 {
+            self.next[index - 1] = Cell::blank(self.next[index - 1].style);
+        }
+        let end = index + width;
+        if self.next.get(end).is_some_and(Cell::is_continuation) && at.col + width < self.size.width {
+            self.next[end] = Cell::blank(self.next[end].style);
+        }
+        self.next[index] = Cell { grapheme: grapheme.to_string(), style };
+        if width == 2 {
+            self.next[index + 1] = Cell { grapheme: String::new(), style };
+        }
+        width
+    }
+
+    /// Where the caret is, if it is shown.
+    pub fn caret(&self) -> Option<Coords> {
+        self.caret_visible.then_some(self.caret)
+    }
+
+    pub fn rows(&self) -> Vec<String> {
+        self.next.chunks(self.size.width.max(1)).map(|row| row.iter().map(|cell| cell.grapheme.as_str()).collect()).collect()
+    }
+
+    /// The escape sequences that bring the terminal from the last frame to
+    /// the next one. Changed cells next to each other are sent as one run,
+    /// and the whole frame is wrapped in a synchronized update, so that
+    /// terminals supporting it show it at once.
+    pub fn flush(&mut self) -> Result<Vec<u8>, Error> {
+        let mut out = Vec::new();
+        if self.stale {
+            queue!(out, Clear(ClearType::All))?;
+            self.previous = vec![Cell::blank(Style::default()); self.next.len()];
+            self.stale = false;
+        }
+        let changed = self.previous != self.next;
+        if changed {
+            queue!(out, BeginSynchronizedUpdate, cursor::Hide)?;
+            self.write_changes(&mut out)?;
+            queue!(out, EndSynchronizedUpdate)?;
+            self.previous.clone_from(&self.next);
+        }
+        #[allow(clippy::as_conversions, clippy::cast_possible_truncation)]
+        queue!(out, cursor::MoveTo(self.caret.col as u16, self.caret.row as u16))?;
+        if self.caret_visible {
+            queue!(out, cursor::Show)?;
+        } else {
+            queue!(out, cursor::Hide)?;
+        }
+        if self.sent_caret_shape != Some(self.caret_shape) {
+            let style = match self.caret_shape {
+                CaretShape::Block => cursor::SetCursorStyle::SteadyBlock,
+                CaretShape::Bar => cursor::SetCursorStyle::SteadyBar,
+                CaretShape::Underline => cursor::SetCursorStyle::SteadyUnderScore,
+            };
+            queue!(out, style)?;
+            self.sent_caret_shape = Some(self.caret_shape);
+        }
+        // The terminal keeps its own title until one is set.
+        if !self.title.is_empty() && self.sent_title.as_ref() != Some(&self.title) {
+            queue!(out, SetTitle(&self.title))?;
+            self.sent_title = Some(self.title.clone());
+        }
+        Ok(out)
+    }
+
+    fn write_changes(&self, out: &mut Vec<u8>) -> Result<(), Error> {
+        let mut cursor = None;
+        let mut current = Style::default();
+        for row in 0..self.size.height {
+            let mut col = 0;
+            while col < self.size.width {
+                let index = row * self.size.width + col;
+                if self.next[index] == self.previous[index] {
+                    col += 1;
+                    continue;
+                }
+                if self.next[index].is_continuation() {
+                    if col == 0 || self.next[index - 1].grapheme.width() != 2 {
+                        col += 1;
+                        continue;
+                    }
+                    col -= 1;
+                }
+                let cell = &self.next[row * self.size.width + col];
+                if cursor != Some(Coords { row, col }) {
+                    #[allow(clippy::as_conversions, clippy::cast_possible_truncation)]
+                    queue!(out, cursor::MoveTo(col as u16, row as u16))?;
+                }
+                if cell.style != current {
+                    self.set_style(out, cell.style)?;
+                    current = cell.style;
+                }
+                queue!(out, style::Print(&cell.grapheme))?;
+                col += cell.grapheme.width().max(1);
+                cursor = Some(Coords { row, col });
+            }
+        }
+        if current != Style::default() {
+            queue!(out, style::SetAttribute(Attribute::Reset))?;
+        }
+        Ok(())
+    }
+
+    fn set_style(&self, out: &mut Vec<u8>, style: Style) -> Result<(), Error> {
+        queue!(out, style::SetAttribute(Attribute::Reset))?;
+        if let Some(color) = style.foreground {
+            queue!(out, style::SetForegroundColor(self.depth.fit(color)))?;
+        }
+        if let Some(color) = style.background {
+            queue!(out, style::SetBackgroundColor(self.depth.fit(color)))?;
+        }
+        if !style.attributes.is_empty() {
+            queue!(out, style::SetAttributes(style.attributes))?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,907 @@
+use std::cell::RefCell;
+use std::fs;
+use std::panic::{self, AssertUnwindSafe};
+use std::path::PathBuf;
+use std::rc::Rc;
+use crossterm::{cursor, queue};
+use crossterm::event::{Event, KeyCode, KeyEvent, KeyModifiers, MouseButton, MouseEvent, MouseEventKind};
+use crossterm::style::Print;
+use crossterm::terminal::{BeginSynchronizedUpdate, EndSynchronizedUpdate};
+use unicode_segmentation::UnicodeSegmentation;
+use unicode_width::UnicodeWidthStr;
+use super::{Buffer, Coords, Editor, Line, Location, Motion, Size, Terminal};
+use super::replay::parse_keys;
+use super::terminal::{Attribute, BackendGuard, CaretShape, Color, ColorDepth, HeadlessBackend, Screen, Snapshot, Style, StyledLine};
+use super::theme::{Group, Theme};
+use super::bufferlist::BufferList;
+use super::layout::{Layout, Rect, Split};
+use super::view::diff;
+use super::view::highlight::TokenKind;
+use super::view::largefile::LargeFile;
+
+/// An editor running headless, and what it showed last.
+struct Harness {
+    editor: Editor,
+    screen: Rc<RefCell<Snapshot>>,
+    _backend: BackendGuard,
+}
+
+impl Harness {
+    /// An editor on an empty buffer, on a screen `width` by `height` cells.
+    fn new(width: usize, height: usize) -> Self {
+        Self::open(&[], width, height)
+    }
+
+    fn open(filenames: &[String], width: usize, height: usize) -> Self {
+        let size = Size { width, height };
+        let (backend, screen) = HeadlessBackend::new(size);
+        let backend = Terminal::use_backend(Box::new(backend));
+        let mut editor = Editor::open(filenames, size);
+        editor.refresh_screen();
+        Self { editor, screen, _backend: backend }
+    }
+
+    fn send(&mut self, event: Event) {
+        self.editor.evaluate_event(event);
+        self.editor.refresh_screen();
+    }
+
+    /// Types `keys`, written in the notation of `parse_keys`.
+    fn type_keys(&mut self, keys: &str) {
+        for event in parse_keys(keys) {
+            self.send(event);
+        }
+    }
+
+    fn mouse(&mut self, kind: MouseEventKind, row: u16, column: u16) {
+        self.send(Event::Mouse(MouseEvent { kind, column, row, modifiers: KeyModifiers::NONE }));
+    }
+
+    fn click(&mut self, row: u16, column: u16) {
+        self.mouse(MouseEventKind::Down(MouseButton::Left), row, column);
+    }
+
+    fn rows(&self) -> Vec<String> {
+        self.screen.borrow().rows.clone()
+    }
+
+    fn caret(&self) -> Option<Coords> {
+        self.screen.borrow().caret
+    }
+
+    /// Resizes the screen to each of `sizes` in turn, checking after each
+    /// resize that the screen has the new size and handing its rows to
+    /// `check`.
+    fn replay_resizes(&mut self, sizes: &[(u16, u16)], check: impl Fn(&[String])) {
+        for &(width, height) in sizes {
+            self.send(Event::Resize(width, height));
+            let rows = self.rows();
+            assert_eq!(rows.len(), usize::from(height), "rows after resizing to {width}x{height}");
+            for row in &rows {
+                assert_eq!(row.width(), usize::from(width), "row {row:?} after resizing to {width}x{height}");
+            }
+            check(&rows);
+        }
+    }
+}
+
+/// A file in the temporary directory, removed again when dropped.
+struct TempFile(PathBuf);
+
+impl TempFile {
+    fn new(name: &str, contents: &str) -> Self {
+        let path = std::env::temp_dir().join(format!("vih-test-{}-{name}", std::process::id()));
+        fs::write(&path, contents).unwrap();
+        Self(path)
+    }
+
+    fn name(&self) -> String {
+        self.0.to_string_lossy().into_owned()
+    }
+
+    fn contents(&self) -> String {
+        fs::read_to_string(&self.0).unwrap()
+    }
+
+    /// The swap file vih keeps for this one, written with `contents`.
+    fn swap(&self, contents: &str) -> Self {
+        let name = self.0.file_name().unwrap().to_string_lossy();
+        let path = self.0.with_file_name(format!(".{name}.swp"));
+        fs::write(&path, contents).unwrap();
+        Self(path)
+    }
+}
+
+impl Drop for TempFile {
+    fn drop(&mut self) {
+        let _ = fs::remove_file(&self.0);
+    }
+}
+
+const SIZES: [(u16, u16); 5] = [(60, 20), (30, 6), (80, 4), (45, 30), (40, 10)];
+
+#[test]
+fn resizing_relayouts_in_every_mode() {
+    for (keys, mode) in [("", "Normal"), ("i", "Insert"), ("v", "Visual")] {
+        let mut harness = Harness::new(40, 10);
+        harness.type_keys("ihello<Esc>");
+        harness.type_keys(keys);
+        harness.replay_resizes(&SIZES, |rows| {
+            let height = rows.len();
+            assert!(rows[0].starts_with("hello"), "text row {:?}", rows[0]);
+            assert!(rows[height - 2].starts_with(mode), "status line {:?}", rows[height - 2]);
+            assert_eq!(rows[height - 1].trim(), "", "message line {:?}", rows[height - 1]);
+        });
+    }
+}
+
+#[test]
+fn resizing_keeps_the_command_line_on_the_bottom_row() {
+    let mut harness = Harness::new(40, 10);
+    harness.type_keys(":set");
+    harness.replay_resizes(&SIZES, |rows| {
+        let height = rows.len();
+        assert!(rows[height - 2].starts_with("Command"), "status line {:?}", rows[height - 2]);
+        assert!(rows[height - 1].starts_with(":set"), "message line {:?}", rows[height - 1]);
+    });
+    assert_eq!(harness.caret(), Some(Coords { row: 9, col: 4 }));
+}
+
+#[test]
+fn resizing_relayouts_every_window() {
+    let mut harness = Harness::new(80, 24);
+    harness.type_keys(":vsplit<CR>:split<CR>");
+    harness.replay_resizes(&[(100, 30), (50, 12), (80, 24)], |rows| {
+        let height = rows.len();
+        let status_lines = rows.iter().filter(|row| row.contains("Normal")).count();
+        assert_eq!(status_lines, 2, "rows with status lines in {rows:#?}");
+        assert_eq!(rows[height - 2].matches("Normal").count(), 2, "bottom status lines {:?}", rows[height - 2]);
+        assert!(rows[..height - 1].iter().all(|row| row.contains('│')), "separator in {rows:#?}");
+    });
+}
+
+#[test]
+fn resizing_to_tiny_screens_does_not_panic() {
+    let mut harness = Harness::new(80, 24);
+    harness.type_keys(":vsplit<CR>:tabnew<CR>ihello");
+    harness.replay_resizes(&[(1, 1), (0, 0), (3, 2), (2, 40), (80, 1), (80, 24)], |_| {});
+}
+
+#[test]
+fn a_count_goes_to_a_tab_page_or_back_as_many() {
+    let file = numbered_lines("tabs.txt");
+    let mut harness = Harness::open(&[file.name()], 40, 10);
+    harness.type_keys(&format!(":tabnew<CR>:tabnew {}<CR>:tabnew<CR>", file.name()));
+    harness.type_keys("gg3gtj");
+    assert_eq!(harness.editor.current_tab, 2);
+    // The second line, under the tab line: the count went to gt, not to j.
+    assert_eq!(harness.caret().map(|caret| caret.row), Some(2));
+    harness.type_keys("1gt");
+    assert_eq!(harness.editor.current_tab, 0);
+    harness.type_keys("2gT");
+    assert_eq!(harness.editor.current_tab, 2);
+    harness.type_keys("gT");
+    assert_eq!(harness.editor.current_tab, 1);
+}
+
+#[test]
+fn recorded_keys_play_back_from_their_register() {
+    let mut harness = Harness::new(40, 10);
+    harness.type_keys("qa");
+    assert!(harness.rows()[8].contains("recording @a"), "status line {:?}", harness.rows()[8]);
+    harness.type_keys("ix<Esc>q");
+    assert!(!harness.rows()[8].contains("recording"), "status line {:?}", harness.rows()[8]);
+    harness.type_keys("2@a");
+    assert_eq!(harness.rows()[0].trim_end(), "xxx");
+    harness.type_keys("@@");
+    assert_eq!(harness.rows()[0].trim_end(), "xxxx");
+    harness.type_keys("qbj@bq@b");
+    assert!(!harness.editor.should_quit);
+}
+
+#[test]
+fn typing_shows_the_text_and_moves_the_caret() {
+    let mut harness = Harness::new(40, 10);
+    harness.type_keys("ihello<CR>world");
+    let rows = harness.rows();
+    assert_eq!(rows[0].trim_end(), "hello");
+    assert_eq!(rows[1].trim_end(), "world");
+    assert_eq!(rows[2].trim_end(), "~");
+    assert!(rows[8].starts_with("Insert"), "status line {:?}", rows[8]);
+    assert_eq!(harness.caret(), Some(Coords { row: 1, col: 5 }));
+}
+
+#[test]
+fn writing_saves_the_buffer_to_its_file() {
+    let file = TempFile::new("write.txt", "one\ntwo\n");
+    let mut harness = Harness::open(&[file.name()], 60, 10);
+    assert_eq!(harness.rows()[1].trim_end(), "two");
+    harness.type_keys("othree<Esc>:w<CR>");
+    assert_eq!(file.contents(), "one\nthree\ntwo\n");
+    assert!(harness.rows()[9].contains("3L written"), "message line {:?}", harness.rows()[9]);
+}
+
+#[test]
+fn shifting_indents_the_counted_lines() {
+    let file = TempFile::new("shift.py", "a\nb\nc\n");
+    let mut harness = Harness::open(&[file.name()], 40, 10);
+    harness.type_keys(":set sw=4<CR>2>>:w<CR>");
+    assert_eq!(file.contents(), "    a\n    b\nc\n");
+    assert_eq!(harness.rows()[0].trim_end(), "    a");
+}
+
+#[test]
+fn new_lines_are_indented_by_the_rules_of_the_filetype() {
+    let cases = [
+        ("indent.rs", "fn main() {<CR>let x = [<CR>1,<CR>];<CR>}", "fn main() {\n  let x = [\n    1,\n  ];\n}\n"),
+        (
+            "indent.c",
+            "int f(void) {<CR>if (x) {<CR>g();<CR>} else {<CR>h();<CR>}<CR>}",
+            "int f(void) {\n  if (x) {\n    g();\n  } else {\n    h();\n  }\n}\n",
+        ),
+        ("indent.json", "{<CR>\"a\": [<CR>1<CR>]<CR>}", "{\n  \"a\": [\n    1\n  ]\n}\n"),
+        (
+            "indent.py",
+            "if a:<CR>b<CR>elif c:<CR>d<CR># no block:<CR>e<CR>else:<CR>f",
+            "if a:\n  b\nelif c:\n  d\n  # no block:\n  e\nelse:\n  f\n",
+        ),
+        (
+            "indent.sh",
+            "for f in *; do<CR>if x; then<CR>y<CR>else<CR>z<CR>fi<CR>done",
+            "for f in *; do\n  if x; then\n    y\n  else\n    z\n  fi\ndone\n",
+        ),
+        ("indent.md", "  a {<CR>b:<CR>}", "  a {\n  b:\n  }\n"),
+        ("indent.txt", "  do<CR>fi", "  do\n  fi\n"),
+    ];
+    for (name, keys, expected) in cases {
+        let file = TempFile::new(name, "");
+        let mut harness = Harness::open(&[file.name()], 40, 10);
+        harness.type_keys(&format!("i{keys}<Esc>:w<CR>"));
+        assert_eq!(file.contents(), expected, "in {name}");
+    }
+}
+
+#[test]
+fn typing_a_closer_takes_the_line_back_out_until_it_is_something_else() {
+    let file = TempFile::new("closer.sh", "");
+    let mut harness = Harness::open(&[file.name()], 40, 10);
+    harness.type_keys("iif x; then<CR>y<CR>fi");
+    assert_eq!(harness.rows()[2].trim_end(), "fi");
+    harness.type_keys("nd");
+    assert_eq!(harness.rows()[2].trim_end(), "  find");
+}
+
+#[test]
+fn clicking_puts_the_caret_on_the_grapheme_under_the_pointer() {
+    let file = TempFile::new("click.txt", "a日本b\nsecond\n");
+    let mut harness = Harness::open(&[file.name()], 40, 10);
+    harness.type_keys(":set nu<CR>");
+    // The gutter takes four columns, and 本 the fourth and fifth after it.
+    harness.click(0, 8);
+    assert_eq!(harness.caret(), Some(Coords { row: 0, col: 7 }));
+    harness.click(6, 30);
+    assert_eq!(harness.caret(), Some(Coords { row: 1, col: 10 }));
+    harness.click(0, 1);
+    assert_eq!(harness.caret(), Some(Coords { row: 0, col: 4 }));
+}
+
+#[test]
+fn clicking_a_wrapped_line_finds_the_row_clicked() {
+    let file = TempFile::new("click-wrap.txt", "0123456789abcdef\n");
+    let mut harness = Harness::open(&[file.name()], 10, 10);
+    harness.type_keys(":set wrap sbr=>><CR>");
+    assert_eq!(harness.rows()[1].trim_end(), ">>abcdef");
+    harness.click(1, 4);
+    harness.type_keys("x");
+    assert_eq!(harness.rows()[1].trim_end(), ">>abdef");
+}
+
+#[test]
+fn dragging_selects_in_visual_mode() {
+    let file = TempFile::new("drag.txt", "hello world\n");
+    let mut harness = Harness::open(&[file.name()], 40, 10);
+    harness.click(0, 0);
+    harness.mouse(MouseEventKind::Drag(MouseButton::Left), 0, 4);
+    assert!(harness.rows()[8].starts_with("Visual"), "status line {:?}", harness.rows()[8]);
+    harness.type_keys("d");
+    assert_eq!(harness.rows()[0].trim_end(), "o world");
+}
+
+#[test]
+fn double_clicking_selects_a_word() {
+    let file = TempFile::new("double-click.txt", "hello world\n");
+    let mut harness = Harness::open(&[file.name()], 40, 10);
+    harness.click(0, 8);
+    harness.click(0, 8);
+    harness.type_keys("d");
+    assert_eq!(harness.rows()[0].trim_end(), "hello");
+}
+
+#[test]
+fn the_wheel_scrolls_without_losing_the_caret() {
+    let lines: Vec<String> = (1..=30).map(|n| format!("line {n}")).collect();
+    let file = TempFile::new("wheel.txt", &(lines.join("\n") + "\n"));
+    let mut harness = Harness::open(&[file.name()], 40, 10);
+    harness.type_keys("3j");
+    harness.mouse(MouseEventKind::ScrollDown, 2, 0);
+    harness.mouse(MouseEventKind::ScrollDown, 2, 0);
+    assert_eq!(harness.rows()[0].trim_end(), "line 7");
+    assert_eq!(harness.caret(), Some(Coords { row: 0, col: 0 }));
+    harness.mouse(MouseEventKind::ScrollUp, 2, 0);
+    harness.mouse(MouseEventKind::ScrollUp, 2, 0);
+    assert_eq!(harness.rows()[0].trim_end(), "line 1");
+    assert_eq!(harness.caret(), Some(Coords { row: 6, col: 0 }));
+}
+
+#[test]
+fn pasting_inserts_the_lines_in_order_as_one_undo_step() {
+    let file = TempFile::new("paste.txt", "ab\n");
+    let mut harness = Harness::open(&[file.name()], 40, 10);
+    harness.type_keys(":set ai<CR>li");
+    harness.send(Event::Paste("1\r  2\r3\n4".to_string()));
+    let rows = harness.rows();
+    assert_eq!(rows[..4].iter().map(|row| row.trim_end()).collect::<Vec<_>>(), ["a1", "  2", "3", "4b"]);
+    assert_eq!(harness.caret(), Some(Coords { row: 3, col: 1 }));
+    harness.type_keys("<Esc>u");
+    assert_eq!(harness.rows()[0].trim_end(), "ab");
+    assert_eq!(harness.rows()[1].trim_end(), "~");
+}
+
+#[test]
+fn the_caret_shape_follows_the_mode() {
+    let mut harness = Harness::new(40, 10);
+    for (keys, shape) in [("", CaretShape::Block), ("i", CaretShape::Bar), ("<Esc>r", CaretShape::Underline), ("<Esc>v", CaretShape::Block)] {
+        harness.type_keys(keys);
+        assert_eq!(harness.screen.borrow().caret_shape, shape, "after {keys:?}");
+    }
+}
+
+#[test]
+fn the_title_names_the_file_and_whether_it_is_modified() {
+    let file = TempFile::new("title.txt", "one\n");
+    let mut harness = Harness::open(&[file.name()], 40, 10);
+    assert_eq!(harness.screen.borrow().title, format!("{} - vih", file.name()));
+    harness.type_keys("x");
+    assert_eq!(harness.screen.borrow().title, format!("{} [+] - vih", file.name()));
+}
+
+/// Thirty lines, `line 1` to `line 30`, in a file.
+fn numbered_lines(name: &str) -> TempFile {
+    let lines: Vec<String> = (1..=30).map(|n| format!("line {n}")).collect();
+    TempFile::new(name, &(lines.join("\n") + "\n"))
+}
+
+#[test]
+fn scroll_keys_scroll_and_keep_the_caret_on_screen() {
+    let file = numbered_lines("scroll.txt");
+    let mut harness = Harness::open(&[file.name()], 40, 10);
+    let check = |harness: &Harness, keys: &str, top: &str, caret_row: usize| {
+        assert_eq!(harness.rows()[0].trim_end(), top, "top row after {keys:?}");
+        assert_eq!(harness.caret(), Some(Coords { row: caret_row, col: 0 }), "caret after {keys:?}");
+    };
+    for (keys, top, caret_row) in [
+        ("<C-e>", "line 2", 0),
+        ("<C-y>", "line 1", 1),
+        ("gg<C-f>", "line 7", 0),
+        ("<C-b>", "line 1", 6),
+        ("gg<C-d>", "line 5", 0),
+        ("<C-u>", "line 1", 0),
+        ("3<C-e>", "line 4", 0),
+    ] {
+        harness.type_keys(keys);
+        check(&harness, keys, top, caret_row);
+    }
+}
+
+#[test]
+fn z_commands_put_the_caret_line_at_the_top_middle_or_bottom() {
+    let file = numbered_lines("z.txt");
+    let mut harness = Harness::open(&[file.name()], 40, 10);
+    for (keys, top, caret_row) in [("10Gzt", "line 10", 0), ("zz", "line 7", 3), ("zb", "line 3", 7), ("1Gzz", "line 1", 0)] {
+        harness.type_keys(keys);
+        assert_eq!(harness.rows()[0].trim_end(), top, "top row after {keys:?}");
+        assert_eq!(harness.caret(), Some(Coords { row: caret_row, col: 0 }), "caret after {keys:?}");
+    }
+}
+
+#[test]
+fn scrolloff_keeps_lines_around_the_caret() {
+    let file = numbered_lines("scrolloff.txt");
+    let mut harness = Harness::open(&[file.name()], 40, 10);
+    harness.type_keys(":set so=2<CR>");
+    for (keys, top, caret_row) in [
+        ("5j", "line 1", 5),
+        ("j", "line 2", 5),
+        ("G", "line 23", 7),
+        ("6k", "line 22", 2),
+        ("zb", "line 19", 5),
+        ("gg", "line 1", 0),
+        ("<C-e>", "line 2", 2),
+    ] {
+        harness.type_keys(keys);
+        assert_eq!(harness.rows()[0].trim_end(), top, "top row after {keys:?}");
+        assert_eq!(harness.caret(), Some(Coords { row: caret_row, col: 0 }), "caret after {keys:?}");
+    }
+}
+
+#[test]
+fn key_notation_names_special_keys() {
+    let key = |code, modifiers| Event::Key(KeyEvent::new(code, modifiers));
+    let char = |c| key(KeyCode::Char(c), KeyModifiers::NONE);
+    assert_eq!(parse_keys("i<Esc>:w<CR>"), [
+        char('i'), key(KeyCode::Esc, KeyModifiers::NONE), char(':'), char('w'), key(KeyCode::Enter, KeyModifiers::NONE),
+    ]);
+    assert_eq!(parse_keys("<C-w>v<lt>a>"), [
+        key(KeyCode::Char('w'), KeyModifiers::CONTROL), char('v'), char('<'), char('a'), char('>'),
+    ]);
+    assert_eq!(parse_keys("2<<\n>>"), [char('2'), char('<'), char('<'), char('>'), char('>')]);
+}
+
+#[test]
+fn replaying_a_script_dumps_the_buffer_and_the_screen() {
+    let file = TempFile::new("replay.txt", "one\n");
+    let output = Editor::replay(&[file.name()], "ohello<Esc>:w<CR>", Size { width: 30, height: 5 }, true);
+    assert_eq!(file.contents(), "one\nhello\n");
+    let (buffer, screen) = output.split_once("--- screen ---\n").unwrap();
+    assert_eq!(buffer, "one\nhello\n");
+    let rows: Vec<&str> = screen.lines().collect();
+    assert_eq!(rows[..3], ["one", "hello", "~"]);
+    assert!(rows[3].starts_with("Normal"), "status line {:?}", rows[3]);
+    assert_eq!(rows[5], "--- block caret at row 2, column 6 ---");
+    assert_eq!(rows[6], format!("--- title: {} - vih ---", file.name()));
+}
+
+#[test]
+fn replaying_stops_when_the_script_quits() {
+    let output = Editor::replay(&[], "ione<Esc>:q!<CR>itwo", Size { width: 30, height: 5 }, false);
+    assert_eq!(output, "one\n");
+}
+
+#[test]
+fn read_only_buffers_ignore_edits() {
+    let mut buffer = Buffer::default();
+    buffer.insert_text("one\ntwo", Location::default());
+    buffer.set_read_only();
+    let end = Location { line_index: 1, grapheme_index: 3 };
+    assert_eq!(buffer.insert_text("x\ny", end), end);
+    buffer.insert_char('x', end);
+    buffer.insert_line(end);
+    buffer.delete(Location::default());
+    buffer.delete_range(Location::default(), end);
+    buffer.replace_lines(&["three".to_string()]);
+    assert_eq!(buffer.text_lines(), ["one", "two"]);
+}
+
+#[test]
+fn motions_move_by_graphemes() {
+    let mut buffer = Buffer::default();
+    buffer.insert_text("\tn\u{303}o\u{308}\n日本", Location::default());
+    let at = |line_index, grapheme_index| Location { grapheme_index, line_index };
+    assert_eq!(Motion::LineEnd.apply(&buffer, at(0, 0)), at(0, 3));
+    assert_eq!(Motion::Right.apply(&buffer, at(0, 3)), at(0, 3));
+    assert_eq!(Motion::Left.apply(&buffer, at(0, 0)), at(0, 0));
+    assert_eq!(Motion::FirstNonBlank.apply(&buffer, at(0, 3)), at(0, 1));
+    assert_eq!(Motion::Down(1).apply(&buffer, at(0, 3)), at(1, 2));
+    assert_eq!(Motion::Down(5).apply(&buffer, at(0, 2)), at(2, 0));
+    assert_eq!(Motion::Up(5).apply(&buffer, at(1, 1)), at(0, 1));
+}
+
+fn indexed(file: &TempFile) -> LargeFile {
+    let large = LargeFile::open(&file.name()).unwrap();
+    while large.is_indexing() {
+        std::thread::yield_now();
+    }
+    large
+}
+
+#[test]
+fn large_files_are_indexed_and_read_a_window_at_a_time() {
+    let file = TempFile::new("indexed", "one\r\ntwo\n\nfour");
+    let mut large = indexed(&file);
+    assert_eq!(large.line_count(), 4);
+    large.ensure_loaded(1..3);
+    assert_eq!(large.line(1).map(ToString::to_string).as_deref(), Some("two"));
+    assert_eq!(large.line(3).map(ToString::to_string).as_deref(), Some("four"));
+    assert_eq!(large.line(0).map(ToString::to_string).as_deref(), Some("one"));
+}
+
+#[test]
+fn large_files_are_searched_in_the_background_and_wrap_around() {
+    let file = TempFile::new("searched", "needle\nhay\nhay needle\n");
+    let large = indexed(&file);
+    let at = |line_index, grapheme_index| Location { grapheme_index, line_index };
+    assert_eq!(large.search("needle", at(0, 0)).wait(), Some(at(2, 4)));
+    assert_eq!(large.search("needle", at(2, 4)).wait(), Some(at(0, 0)));
+    assert_eq!(large.search("straw", at(0, 0)).wait(), None);
+}
+
+fn lines(text: &str) -> Vec<String> {
+    text.split_whitespace().map(String::from).collect()
+}
+
+#[test]
+fn diffs_show_changes_with_three_lines_of_context() {
+    let old = lines("1 2 3 4 5 6 7 8 9 10 11 12 13 14 15");
+    let new = lines("1 2 3 4 5 6 x 8 9 10 11 12 13 14 15 16");
+    assert_eq!(
+        diff::unified(&old, &new),
+        ["@@ -4,7 +4,7 @@", " 4", " 5", " 6", "-7", "+x", " 8", " 9", " 10", "@@ -13,3 +13,4 @@", " 13", " 14", " 15", "+16"],
+    );
+    assert!(diff::unified(&old, &old).is_empty());
+}
+
+#[test]
+fn diffs_find_the_shortest_edit() {
+    let old = lines("a b c a b b a");
+    let new = lines("c b a b a c");
+    let changes = diff::unified(&old, &new);
+    assert_eq!(changes.iter().filter(|line| !line.starts_with([' ', '@'])).count(), 5);
+    let kept = |mark| changes.iter().filter(|line| !line.starts_with(['@', mark])).map(|line| &line[1..]).collect::<Vec<_>>();
+    assert_eq!(kept('+'), ["a", "b", "c", "a", "b", "b", "a"]);
+    assert_eq!(kept('-'), ["c", "b", "a", "b", "a", "c"]);
+}
+
+#[test]
+fn diffs_too_big_to_work_out_replace_the_whole_middle() {
+    let old: Vec<String> = (0..3000).map(|n| format!("old {n}")).collect();
+    let new: Vec<String> = (0..3000).map(|n| format!("new {n}")).collect();
+    let changes = diff::unified(&old, &new);
+    assert_eq!(changes[0], "@@ -1,3000 +1,3000 @@");
+    assert!(changes[1..=3000].iter().all(|line| line.starts_with('-')));
+    assert!(changes[3001..].iter().all(|line| line.starts_with('+')));
+}
+
+#[test]
+fn a_swap_file_left_behind_can_be_recovered() {
+    let file = TempFile::new("recover.txt", "on disk\n");
+    let _swap = file.swap("recovered\n");
+    let mut harness = Harness::open(&[file.name()], 60, 10);
+    assert!(harness.rows().iter().any(|row| row.contains("Found swap file")));
+    assert!(harness.rows().iter().any(|row| row.trim_end() == "+recovered"));
+    harness.type_keys("r");
+    assert_eq!(harness.rows()[0].trim_end(), "recovered");
+    assert!(harness.screen.borrow().title.contains("[+]"));
+}
+
+#[test]
+fn unsaved_edits_are_flushed_to_the_swap_file_on_a_panic() {
+    let file = TempFile::new("flush.txt", "one\n");
+    let mut harness = Harness::open(&[file.name()], 40, 10);
+    harness.type_keys("itwo <Esc>");
+    let swap = file.swap("");
+    BufferList::flush_swaps();
+    assert_eq!(swap.contents(), "two one\n");
+}
+
+#[test]
+fn edits_reach_the_swap_file_when_the_panic_holds_the_buffer() {
+    let file = TempFile::new("held.txt", "one\n");
+    let mut harness = Harness::open(&[file.name()], 40, 10);
+    let swap = file.swap("");
+    harness.type_keys("itwo <Esc>");
+    harness.editor.write_swaps();
+    harness.type_keys("ithree <Esc>");
+    harness.editor.write_swaps();
+    assert_eq!(swap.contents(), "two one\n");
+    let buffer = harness.editor.view().buffer();
+    let previous_hook = panic::take_hook();
+    panic::set_hook(Box::new(|_| BufferList::flush_swaps()));
+    let result = panic::catch_unwind(AssertUnwindSafe(|| {
+        let _editing = buffer.borrow_mut();
+        panic!("in the middle of an edit");
+    }));
+    panic::set_hook(previous_hook);
+    assert!(result.is_err());
+    assert_eq!(swap.contents(), "two three one\n");
+}
+
+#[test]
+fn files_changed_on_disk_are_reloaded_and_deleted_ones_reported() {
+    let file = TempFile::new("changed.txt", "before\n");
+    let mut harness = Harness::open(&[file.name()], 60, 10);
+    fs::write(&file.0, "after\n").unwrap();
+    harness.send(Event::FocusGained);
+    assert_eq!(harness.rows()[0].trim_end(), "after");
+    fs::remove_file(&file.0).unwrap();
+    harness.send(Event::FocusGained);
+    assert!(harness.rows()[9].contains("was deleted on disk"), "{:?}", harness.rows()[9]);
+    assert!(harness.screen.borrow().title.contains("[+]"));
+    harness.type_keys(":w<CR>");
+    assert_eq!(file.contents(), "after\n");
+}
+
+#[test]
+fn regaining_focus_checks_hidden_buffers_too() {
+    let clean = TempFile::new("clean.txt", "clean\n");
+    let edited = TempFile::new("edited.txt", "edited\n");
+    let shown = TempFile::new("shown.txt", "shown\n");
+    let mut harness = Harness::open(&[clean.name()], 60, 10);
+    harness.type_keys(&format!(":e {}<CR>ix<Esc>:e {}<CR>", edited.name(), shown.name()));
+    fs::write(&clean.0, "clean, reloaded\n").unwrap();
+    fs::write(&edited.0, "edited elsewhere\n").unwrap();
+    harness.send(Event::FocusGained);
+    assert!(harness.rows()[9].contains("changed on disk"), "{:?}", harness.rows()[9]);
+    harness.type_keys(&format!(":e {}<CR>", clean.name()));
+    assert_eq!(harness.rows()[0].trim_end(), "clean, reloaded");
+    harness.type_keys(&format!(":e {}<CR>", edited.name()));
+    assert_eq!(harness.rows()[0].trim_end(), "xedited");
+    assert!(harness.rows().iter().any(|row| row.contains("changed on disk: [r]eload")), "{:#?}", harness.rows());
+    harness.type_keys("r");
+    assert_eq!(harness.rows()[0].trim_end(), "edited elsewhere");
+}
+
+#[test]
+fn undo_and_redo_step_through_whole_commands() {
+    let file = TempFile::new("undo.txt", "one\n");
+    let mut harness = Harness::open(&[file.name()], 40, 10);
+    let text = |harness: &Harness| harness.rows()[..3].iter().map(|row| row.trim_end().to_string()).collect::<Vec<_>>();
+    harness.type_keys("ia<CR>b<Esc>othree<Esc>");
+    assert_eq!(text(&harness), ["a", "bone", "three"]);
+    harness.type_keys("u");
+    assert_eq!(text(&harness), ["a", "bone", "~"]);
+    harness.type_keys("u");
+    assert_eq!(text(&harness), ["one", "~", "~"]);
+    harness.type_keys("u<C-r>");
+    assert_eq!(text(&harness), ["a", "bone", "~"]);
+    harness.type_keys("<C-r>");
+    assert_eq!(text(&harness), ["a", "bone", "three"]);
+}
+
+#[test]
+fn undoing_back_to_the_saved_text_clears_the_modified_flag() {
+    let file = TempFile::new("saved.txt", "one\n");
+    let mut harness = Harness::open(&[file.name()], 40, 10);
+    let modified = |harness: &Harness| harness.screen.borrow().title.contains("[+]");
+    harness.type_keys("x");
+    assert!(modified(&harness));
+    harness.type_keys("u");
+    assert!(!modified(&harness));
+    harness.type_keys("<C-r>");
+    assert!(modified(&harness));
+    harness.type_keys(":w<CR>u");
+    assert!(modified(&harness));
+    harness.type_keys("<C-r>");
+    assert!(!modified(&harness));
+}
+
+#[test]
+fn editing_a_file_only_reuses_a_buffer_for_the_same_path() {
+    let longer = TempFile::new("foobar.txt", "foobar\n");
+    let shorter = longer.name().replace("foobar.txt", "foo");
+    let mut harness = Harness::open(&[longer.name()], 80, 10);
+    harness.type_keys(&format!(":e {shorter}<CR>"));
+    assert_eq!(harness.screen.borrow().title, format!("{shorter} - vih"));
+    let roundabout = longer.name().replace("/vih-test", "/./vih-test");
+    harness.type_keys(&format!(":e {roundabout}<CR>:ls<CR>"));
+    assert_eq!(harness.rows().iter().filter(|row| row.contains('"')).count(), 2);
+}
+
+#[test]
+fn files_that_cannot_be_opened_are_reported() {
+    let directory = std::env::temp_dir().to_string_lossy().into_owned();
+    let harness = Harness::open(&[directory], 60, 10);
+    assert!(harness.rows()[9].starts_with("Could not open"), "{:?}", harness.rows()[9]);
+}
+
+fn rect(row: usize, col: usize, width: usize, height: usize) -> Rect {
+    Rect { origin: Coords { row, col }, size: Size { width, height } }
+}
+
+#[test]
+fn layouts_share_the_space_between_splits_and_separators() {
+    let mut layout = Layout::new(0);
+    layout.split(0, 1, Split::Vertical);
+    layout.split(0, 2, Split::Horizontal);
+    let (windows, separators) = layout.arrange(rect(0, 0, 81, 20));
+    assert_eq!(windows, [(1, rect(0, 0, 40, 20)), (2, rect(0, 41, 40, 10)), (0, rect(10, 41, 40, 10))]);
+    assert_eq!(separators, [rect(0, 40, 1, 20)]);
+}
+
+#[test]
+fn layouts_keep_their_proportions_when_the_screen_changes() {
+    let mut layout = Layout::new(0);
+    layout.split(0, 1, Split::Vertical);
+    layout.arrange(rect(0, 0, 81, 20));
+    layout.resize(1, Split::Vertical, 5);
+    let widths = |layout: &mut Layout, width| layout.arrange(rect(0, 0, width, 20)).0.iter().map(|(_, rect)| rect.size.width).collect::<Vec<_>>();
+    assert_eq!(widths(&mut layout, 81), [45, 35]);
+    assert_eq!(widths(&mut layout, 161), [90, 70]);
+    layout.split(0, 2, Split::Vertical);
+    let tiny = widths(&mut layout, 6);
+    assert_eq!(tiny.iter().sum::<usize>(), 4);
+    assert!(tiny.iter().all(|width| *width > 0), "{tiny:?}");
+}
+
+#[test]
+fn lines_wrap_into_rows_of_the_given_widths() {
+    let wrap = |text, width, continuation_width, at_word| Line::from(text).wrap(width, continuation_width, at_word, 8);
+    assert_eq!(wrap("abcdefghij", 4, 4, false), [0, 4, 8]);
+    assert_eq!(wrap("abcdefgh", 4, 2, false), [0, 4, 6]);
+    assert_eq!(wrap("one two three", 10, 10, false), [0, 10]);
+    assert_eq!(wrap("one two three", 10, 10, true), [0, 8]);
+    assert_eq!(wrap("日本語", 5, 5, false), [0, 2]);
+    assert_eq!(wrap("\tx", 4, 4, false), [0, 1]);
+    assert_eq!(wrap("", 4, 4, false), [0]);
+}
+
+/// A Rust buffer holding `lines`, highlighted down to the end.
+fn highlighted(lines: &[&str]) -> Buffer {
+    let mut buffer = Buffer::named("highlighted.rs");
+    buffer.insert_text(&lines.join("\n"), Location::default());
+    buffer.update_highlights(lines.len());
+    buffer
+}
+
+/// The text and kind of every token on line `line_index` of `buffer`.
+fn tokens(buffer: &Buffer, line_index: usize) -> Vec<(String, TokenKind)> {
+    let line = buffer.line(line_index).unwrap();
+    buffer.tokens(line_index).iter().map(|token| (line.text(token.range.clone()), token.kind)).collect()
+}
+
+#[test]
+fn grammars_pick_out_keywords_strings_numbers_and_comments() {
+    let buffer = highlighted(&["let s: &str = \"a \\\" b\"; 42 // done"]);
+    assert_eq!(
+        tokens(&buffer, 0),
+        [
+            ("let".to_string(), TokenKind::Keyword),
+            ("str".to_string(), TokenKind::Type),
+            ("\"a \\\" b\"".to_string(), TokenKind::String),
+            ("42".to_string(), TokenKind::Number),
+            ("// done".to_string(), TokenKind::Comment),
+        ],
+    );
+}
+
+#[test]
+fn block_comments_carry_over_to_the_lines_after_them() {
+    let mut buffer = highlighted(&["/* open", "still", "*/ fn"]);
+    assert_eq!(tokens(&buffer, 1), [("still".to_string(), TokenKind::Comment)]);
+    assert_eq!(tokens(&buffer, 2), [("*/".to_string(), TokenKind::Comment), ("fn".to_string(), TokenKind::Keyword)]);
+    buffer.delete_line(0, 0, 2);
+    buffer.update_highlights(3);
+    assert!(tokens(&buffer, 1).is_empty());
+    assert_eq!(tokens(&buffer, 2), [("fn".to_string(), TokenKind::Keyword)]);
+    buffer.insert_text("/*", Location::default());
+    buffer.update_highlights(3);
+    assert_eq!(tokens(&buffer, 1), [("still".to_string(), TokenKind::Comment)]);
+}
+
+#[test]
+fn overlapping_highlights_take_the_colors_of_the_higher_priority() {
+    let mut line = StyledLine::plain("abcdef");
+    let high = Style::foreground(Color::Green).with(Attribute::Underlined);
+    let low = Style { foreground: Some(Color::Red), ..Style::background(Color::Blue) }.with(Attribute::Bold);
+    line.highlight(2..6, high, 2);
+    line.highlight(0..4, low, 1);
+    let both = Style { background: Some(Color::Blue), ..high }.with(Attribute::Bold);
+    assert_eq!(line.runs(), [("ab".to_string(), low), ("cd".to_string(), both), ("ef".to_string(), high)]);
+}
+
+/// A screen of `width` by one row that has sent its first frame, `text`.
+fn flushed_screen(width: usize, text: &str) -> Screen {
+    let mut screen = Screen::default();
+    screen.resize(Size { width, height: 1 });
+    put_text(&mut screen, 0, text);
+    screen.flush().unwrap();
+    screen
+}
+
+fn put_text(screen: &mut Screen, col: usize, text: &str) {
+    let mut at = Coords { row: 0, col };
+    for grapheme in text.graphemes(true) {
+        at.col += screen.put(at, grapheme, Style::default());
+    }
+}
+
+/// What a frame sends when `changes` is the only run: a column and the
+/// text from there.
+fn frame_bytes(changes: Option<(u16, &str)>) -> Vec<u8> {
+    let mut out = Vec::new();
+    if let Some((col, text)) = changes {
+        queue!(out, BeginSynchronizedUpdate, cursor::Hide, cursor::MoveTo(col, 0), Print(text), EndSynchronizedUpdate).unwrap();
+    }
+    queue!(out, cursor::MoveTo(0, 0), cursor::Hide).unwrap();
+    out
+}
+
+#[test]
+fn an_unchanged_frame_sends_only_the_caret() {
+    let mut screen = flushed_screen(10, "hello");
+    put_text(&mut screen, 0, "hello");
+    assert_eq!(screen.flush().unwrap(), frame_bytes(None));
+}
+
+#[test]
+fn a_changed_cell_is_sent_as_one_run() {
+    let mut screen = flushed_screen(10, "hello");
+    put_text(&mut screen, 1, "a");
+    assert_eq!(screen.flush().unwrap(), frame_bytes(Some((1, "a"))));
+}
+
+#[test]
+fn a_narrow_grapheme_over_a_wide_one_blanks_its_other_half() {
+    let mut screen = flushed_screen(10, "a日b");
+    put_text(&mut screen, 1, "x");
+    assert_eq!(screen.rows(), ["ax b      "]);
+    assert_eq!(screen.flush().unwrap(), frame_bytes(Some((1, "x "))));
+    let mut screen = flushed_screen(10, "a日b");
+    put_text(&mut screen, 2, "y");
+    assert_eq!(screen.rows(), ["a yb      "]);
+    assert_eq!(screen.flush().unwrap(), frame_bytes(Some((1, " y"))));
+}
+
+#[test]
+fn colors_fit_the_depth_of_the_terminal() {
+    let red = Color::Rgb { r: 250, g: 10, b: 10 };
+    let gray = Color::Rgb { r: 128, g: 128, b: 128 };
+    assert_eq!(ColorDepth::TrueColor.fit(red), red);
+    assert_eq!(ColorDepth::Ansi256.fit(red), Color::AnsiValue(196));
+    assert_eq!(ColorDepth::Ansi256.fit(gray), Color::AnsiValue(244));
+    assert_eq!(ColorDepth::Ansi256.fit(Color::AnsiValue(100)), Color::AnsiValue(100));
+    assert_eq!(ColorDepth::Ansi16.fit(red), Color::Red);
+    assert_eq!(ColorDepth::Ansi16.fit(Color::AnsiValue(196)), Color::Red);
+    assert_eq!(ColorDepth::Ansi16.fit(Color::AnsiValue(3)), Color::AnsiValue(3));
+    assert_eq!(ColorDepth::Ansi16.fit(Color::DarkBlue), Color::DarkBlue);
+}
+
+#[test]
+fn themes_set_the_groups_they_name() {
+    let theme = Theme::parse("test", "# A comment\nComment fg=#808080 bg=236 italic\n\nKeyword fg=darkblue bold").unwrap();
+    let comment = Style { background: Some(Color::AnsiValue(236)), ..Style::foreground(Color::Rgb { r: 128, g: 128, b: 128 }) };
+    assert_eq!(theme.style(Group::Comment), comment.with(Attribute::Italic));
+    assert_eq!(theme.style(Group::Keyword), Style::foreground(Color::DarkBlue).with(Attribute::Bold));
+    assert_eq!(theme.style(Group::String), Style::foreground(Color::DarkGreen));
+    assert_eq!(theme.style(Group::Normal), Style::default());
+}
+
+#[test]
+fn themes_report_the_line_they_cannot_read() {
+    let error = |text| Theme::parse("test", text).err().unwrap();
+    assert_eq!(error("Bogus fg=red"), "test.theme line 1: Unknown group Bogus");
+    assert_eq!(error("Normal\nComment fg=#12"), "test.theme line 2: Invalid color #12");
+    assert_eq!(error("Comment blink"), "test.theme line 1: Unknown attribute blink");
+    assert_eq!(error("Comment size=2"), "test.theme line 1: Unknown setting size=2");
+}
+
+#[test]
+fn writing_keeps_the_line_endings_the_file_had() {
+    let dos = TempFile::new("dos.txt", "one\r\ntwo\r\n");
+    let unix = TempFile::new("unix.txt", "one\ntwo\n");
+    for (file, expected) in [(&dos, "xone\r\ntwo\r\n"), (&unix, "xone\ntwo\n")] {
+        let mut harness = Harness::open(&[file.name()], 40, 10);
+        harness.type_keys("ix<Esc>:w<CR>");
+        assert_eq!(file.contents(), expected);
+    }
+}
+
+#[test]
+fn replaying_gives_the_backend_back_afterwards() {
+    let (backend, screen) = HeadlessBackend::new(Size { width: 30, height: 5 });
+    let _backend = Terminal::use_backend(Box::new(backend));
+    let _ = Editor::replay(&[], "ione", Size { width: 20, height: 4 }, true);
+    Terminal::set_title("after");
+    Terminal::execute().unwrap();
+    assert_eq!(screen.borrow().title, "after");
+    assert_eq!(Terminal::size().unwrap(), Size { width: 30, height: 5 });
+}
+
+#[test]
+fn quitting_refuses_while_a_hidden_buffer_is_modified() {
+    let hidden = TempFile::new("hidden.txt", "one\n");
+    let shown = TempFile::new("shown.txt", "two\n");
+    let mut harness = Harness::open(&[hidden.name()], 60, 10);
+    harness.type_keys(&format!("ix<Esc>:e {}<CR>", shown.name()));
+    BufferList::flush_swaps();
+    let swap = hidden.swap("xone\n");
+    for command in [":q<CR>", ":qa<CR>"] {
+        harness.type_keys(command);
+        assert!(!harness.editor.should_quit, "after {command}");
+        assert!(harness.rows()[9].starts_with("No write since last change for"), "{:?}", harness.rows()[9]);
+        assert!(swap.0.exists());
+    }
+    harness.type_keys(":qa!<CR>");
+    assert!(harness.editor.should_quit);
+    assert!(!swap.0.exists());
+    assert_eq!(hidden.contents(), "one\n");
+}
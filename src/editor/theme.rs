@@ -0,0 +1,201 @@
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::fs::read_to_string;
+use std::ops::Range;
+use std::path::PathBuf;
+use super::terminal::{Attribute, Color, Style, Terminal};
+
+/// Everything on screen is drawn in the style of one of these groups, which
+/// a theme maps to colors and attributes.
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Debug)]
+pub enum Group {
+    Normal,
+    Visual,
+    Search,
+    LineNr,
+    /// The `~` rows past the end of the buffer.
+    NonText,
+    StatusLine,
+    TabLine,
+    TabLineSel,
+    VertSplit,
+    ErrorMsg,
+    WarningMsg,
+    Comment,
+    String,
+    Number,
+    Keyword,
+    Type,
+    Constant,
+    Key,
+    Variable,
+    PreProc,
+    Title,
+    Emphasis,
+    Code,
+    Link,
+}
+
+impl Group {
+    const ALL: [Self; 24] = [
+        Self::Normal, Self::Visual, Self::Search, Self::LineNr, Self::NonText, Self::StatusLine, Self::TabLine,
+        Self::TabLineSel, Self::VertSplit, Self::ErrorMsg, Self::WarningMsg, Self::Comment, Self::String, Self::Number, Self::Keyword, Self::Type,
+        Self::Constant, Self::Key, Self::Variable, Self::PreProc, Self::Title, Self::Emphasis, Self::Code, Self::Link,
+    ];
+
+    fn from_name(name: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|group| format!("{group:?}") == name)
+    }
+
+    /// The style of the group in the current theme.
+    pub fn style(self) -> Style {
+        CURRENT.with_borrow(|theme| theme.style(self))
+    }
+}
+
+thread_local! {
+    static CURRENT: RefCell<Theme> = RefCell::new(Theme::default());
+}
+
+/// The styles of the highlight groups, read from a theme file in the
+/// `colors` directory of the config dir. Groups a theme leaves out keep
+/// their default style.
+pub struct Theme {
+    name: String,
+    styles: HashMap<Group, Style>,
+}
+
+impl Default for Theme {
+    fn default() -> Self {
+        let styles = [
+            (Group::Visual, Style::background(Color::DarkBlue)),
+            (Group::Search, Style { foreground: Some(Color::Black), ..Style::background(Color::DarkYellow) }),
+            (Group::TabLineSel, Style::background(Color::DarkBlue)),
+            (Group::ErrorMsg, Style { foreground: Some(Color::White), ..Style::background(Color::DarkRed) }),
+            (Group::WarningMsg, Style::foreground(Color::Red)),
+            (Group::Comment, Style::foreground(Color::DarkGrey)),
+            (Group::String, Style::foreground(Color::DarkGreen)),
+            (Group::Code, Style::foreground(Color::DarkGreen)),
+            (Group::Number, Style::foreground(Color::DarkMagenta)),
+            (Group::Constant, Style::foreground(Color::DarkMagenta)),
+            (Group::Keyword, Style::foreground(Color::DarkYellow)),
+            (Group::Title, Style::foreground(Color::DarkYellow).with(Attribute::Bold)),
+            (Group::Type, Style::foreground(Color::DarkCyan)),
+            (Group::Variable, Style::foreground(Color::DarkCyan)),
+            (Group::Key, Style::foreground(Color::Blue)),
+            (Group::Link, Style::foreground(Color::Blue).with(Attribute::Underlined)),
+            (Group::PreProc, Style::foreground(Color::Magenta)),
+            (Group::Emphasis, Style::default().with(Attribute::Italic)),
+        ];
+        Self { name: "default".to_string(), styles: styles.into_iter().collect() }
+    }
+}
+
+impl Theme {
+    /// Loads the theme called `name`: the built-in one for `default`, and
+    /// `<config dir>/colors/<name>.theme` otherwise.
+    pub fn load(name: &str) -> Result<Self, String> {
+        if name == "default" {
+            return Ok(Self::default());
+        }
+        let path = config_dir()
+            .map(|dir| dir.join("colors").join(format!("{name}.theme")))
+            .ok_or_else(|| "No config directory".to_string())?;
+        let text = read_to_string(&path).map_err(|_| format!("Cannot find color scheme '{name}'"))?;
+        Self::parse(name, &text)
+    }
+
+    /// Reads a theme with one group per line, followed by its colors and
+    /// attributes, as in `Comment fg=#808080 bg=236 italic`. Colors are
+    /// `#rrggbb`, numbers of the 256-color palette or names such as
+    /// `darkblue`; attributes are `bold`, `italic`, `underline` and
+    /// `reverse`. Lines starting with `#` are comments.
+    pub(super) fn parse(name: &str, text: &str) -> Result<Self, String> {
+        let mut theme = Self { name: name.to_string(), ..Self::default() };
+        for (number, line) in text.lines().enumerate() {
+            let error = |message: String| format!("{name}.theme line {}: {message}", number + 1);
+            let mut words = line.split_whitespace();
+            let Some(group) = words.next().filter(|word| !word.starts_with('#')) else {
+                continue;
+            };
+            let group = Group::from_name(group).ok_or_else(|| error(format!("Unknown group {group}")))?;
+            let mut style = Style::default();
+            for word in words {
+                match word.split_once('=') {
+                    Some(("fg", color)) => style.foreground = Some(parse_color(color).ok_or_else(|| error(format!("Invalid color {color}")))?),
+                    Some(("bg", color)) => style.background = Some(parse_color(color).ok_or_else(|| error(format!("Invalid color {color}")))?),
+                    None => style = style.with(parse_attribute(word).ok_or_else(|| error(format!("Unknown attribute {word}")))?),
+                    Some(_) => return Err(error(format!("Unknown setting {word}"))),
+                }
+            }
+            theme.styles.insert(group, style);
+        }
+        Ok(theme)
+    }
+
+    pub(super) fn style(&self, group: Group) -> Style {
+        self.styles.get(&group).copied().unwrap_or_default()
+    }
+
+    /// Makes this the theme everything is drawn with.
+    pub fn apply(self) {
+        Terminal::set_base_style(self.style(Group::Normal));
+        CURRENT.set(self);
+    }
+
+    pub fn current_name() -> String {
+        CURRENT.with_borrow(|theme| theme.name.clone())
+    }
+}
+
+/// `$XDG_CONFIG_HOME/vih`, or `~/.config/vih`.
+fn config_dir() -> Option<PathBuf> {
+    let base = std::env::var_os("XDG_CONFIG_HOME")
+        .filter(|dir| !dir.is_empty())
+        .map(PathBuf::from)
+        .or_else(|| std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".config")))?;
+    Some(base.join(env!("CARGO_PKG_NAME")))
+}
+
+fn parse_color(text: &str) -> Option<Color> {
+    if let Some(hex) = text.strip_prefix('#') {
+        if hex.len() != 6 {
+            return None;
+        }
+        let channel = |range: Range<usize>| u8::from_str_radix(hex.get(range)?, 16).ok();
+        return Some(Color::Rgb { r: channel(0..2)?, g: channel(2..4)?, b: channel(4..6)? });
+    }
+    if let Ok(value) = text.parse::<u8>() {
+        return Some(Color::AnsiValue(value));
+    }
+    let color = match text.to_ascii_lowercase().as_str() {
+        "black" => Color::Black,
+        "darkgrey" | "darkgray" => Color::DarkGrey,
+        "red" => Color::Red,
+        "darkred" => Color::DarkRed,
+        "green" => Color::Green,
+        "darkgreen" => Color::DarkGreen,
+        "yellow" => Color::Yellow,
+        "darkyellow" | "brown" => Color::DarkYellow,
+        "blue" => Color::Blue,
+        "darkblue" => Color::DarkBlue,
+        "magenta" => Color::Magenta,
+        "darkmagenta" => Color::DarkMagenta,
+        "cyan" => Color::Cyan,
+        "darkcyan" => Color::DarkCyan,
+        "white" => Color::White,
+        "grey" | "gray" => Color::Grey,
+        _ => return None,
+    };
+    Some(color)
+}
+
+fn parse_attribute(text: &str) -> Option<Attribute> {
+    match text {
+        "bold" => Some(Attribute::Bold),
+        "italic" => Some(Attribute::Italic),
+        "underline" => Some(Attribute::Underlined),
+        "reverse" => Some(Attribute::Reverse),
+        _ => None,
+    }
+}
@@ -1,19 +1,49 @@
-use arboard::Clipboard;
 use crossterm::event::Event;
+use std::cell::RefCell;
 use std::cmp::min;
+use std::ops::Range;
+use std::rc::Rc;
+use std::time::{Duration, Instant};
+use unicode_segmentation::UnicodeSegmentation;
 use super::{
     editorcommand::{Direction, EditorCommand},
-    terminal::{Size, Terminal, Coords},
+    terminal::{CaretShape, Coords, Size, Style, StyledLine, Terminal},
+    theme::Group,
+    messageline::Severity,
     DocumentStatus,
 };
-use self::line::Line;
+pub use self::line::Line;
 
 mod buffer;
+pub(super) mod diff;
+mod filetype;
+mod gutter;
+pub(super) mod highlight;
+mod indent;
+pub(super) mod largefile;
+mod motion;
+mod options;
+mod prompt;
 mod selection;
+mod swap;
+use gutter::Gutter;
+use highlight::Token;
+use indent::IndentRules;
+use largefile::BackgroundSearch;
+use options::Options;
+use prompt::{Prompt, PromptKind, ESCAPE};
 use selection::Selection;
-use buffer::Buffer;
+pub use buffer::{Buffer, LineEnding};
+pub(super) use buffer::DiskChange;
+pub use motion::Motion;
+pub(super) use swap::Swap;
 mod line;
 
+/// How highlights rank where they overlap on screen; higher ones win.
+const SYNTAX_PRIORITY: u8 = 10;
+const SEARCH_PRIORITY: u8 = 30;
+const SELECTION_PRIORITY: u8 = 50;
+
 const NAME: &str = env!("CARGO_PKG_NAME");
 const VERSION: &str = env!("CARGO_PKG_VERSION");
 
@@ -22,17 +52,76 @@ pub enum Bmode {
     Insert,
     Visual,
     Replace,
+    Command,
+    Search,
+}
+
+/// A second click on the same spot within this time is a double click.
+const DOUBLE_CLICK_TIME: Duration = Duration::from_millis(500);
+/// The keys that scroll the view when typed with Ctrl.
+const SCROLL_KEYS: &str = "eydufb";
+/// How many rows a turn of the mouse wheel scrolls.
+const WHEEL_SCROLL_ROWS: usize = 3;
+
+/// The key that starts a window command, `Ctrl-w`.
+const WINDOW_PREFIX: char = '\u{17}';
+
+/// Things the view cannot deal with on its own and hands back to the editor.
+pub enum Request {
+    Quit,
+    Command(String),
+    /// The key typed after `Ctrl-w`.
+    Window(char),
+    /// `q{register}` starts recording keys into the register, and `q`
+    /// alone stops it.
+    Record(Option<char>),
+    /// `{count}@{register}` plays the keys in the register `count` times;
+    /// `@@` plays the register played last.
+    Play { register: char, count: usize },
+}
+
+/// Where the caret and the viewport were in a buffer, so that switching back
+/// to it puts the view where it was left.
+#[derive(Copy, Clone, Default)]
+pub struct Position {
+    pub text_location: Location,
+    pub scroll_offset: Coords,
 }
 
 pub struct View {
-    buffer: Buffer,
+    buffer: Rc<RefCell<Buffer>>,
     needs_redraw: bool,
+    /// The buffer revision last drawn, so that changes made through another
+    /// view onto the same buffer get drawn here too.
+    rendered_revision: Option<u64>,
+    rendered_gutter: Option<Gutter>,
     text_location: Location,
     scroll_offset: Coords,
+    /// With `wrap`, how many rows of the first line shown are scrolled past.
+    top_subrow: usize,
+    origin: Coords,
     size: Size,
     selection: Selection,
     bmode: Bmode,
-    clipboard: String,
+    clipboard: Rc<RefCell<String>>,
+    /// Messages waiting to be shown on the message line.
+    messages: Vec<(Severity, String)>,
+    /// Keys typed so far of a command that takes more than one, like `gg`.
+    pending_keys: String,
+    /// A count typed before a command, as in `3>>`.
+    count: Option<usize>,
+    /// The register keys are being recorded into, which the editor keeps
+    /// every view told of.
+    recording: Option<char>,
+    command_line: String,
+    last_search: String,
+    /// A search through a large file still going on in the background.
+    pending_search: Option<BackgroundSearch>,
+    prompt: Option<Prompt>,
+    options: Options,
+    /// When and where the left button was last pressed, to tell a double
+    /// click from two single ones.
+    last_click: Option<(Instant, Location)>,
 }
 
 #[derive(Copy, Clone, Default, PartialEq, Debug)]
@@ -43,61 +132,88 @@ pub struct Location {
 
 impl View {
     pub fn render(&mut self) {
+        if let Some(search) = self.pending_search.take_if(|search| search.is_done()) {
+            self.show_search_result(search.wait());
+        }
+        let revision = self.buffer.borrow().revision();
+        if self.rendered_revision != Some(revision) {
+            self.rendered_revision = Some(revision);
+            self.snap_to_valid_line();
+            self.snap_to_valid_grapheme();
+            self.scroll_text_location_into_view();
+            self.needs_redraw = true;
+        }
+        let gutter = self.gutter();
+        if self.rendered_gutter.as_ref() != Some(&gutter) {
+            self.rendered_gutter = Some(gutter);
+            self.scroll_text_location_into_view();
+            self.needs_redraw = true;
+        }
         if !self.needs_redraw {return;}
         let Size {height, width} = self.size;
         if height == 0 || width == 0 {return;}
-        if self.buffer.is_empty() {
+        let first = self.scroll_offset.row;
+        self.buffer.borrow_mut().ensure_loaded(first..first.saturating_add(height));
+        self.buffer.borrow_mut().update_highlights(first.saturating_add(height));
+        if let Some(prompt) = self.prompt.as_ref().filter(|prompt| !prompt.preview.is_empty()) {
+            self.render_preview(prompt);
+        } else if self.buffer.borrow().is_empty() {
             self.render_welcome_screen();
         } else {
             self.render_buffer();
         }
+        if let Some(prompt) = &self.prompt {
+            self.render_prompt_message(prompt);
+        }
         self.needs_redraw = false;
     }
 
-    pub fn handle_command(&mut self, event: Event) -> bool {
+    pub fn handle_command(&mut self, event: Event) -> Option<Request> {
+        if self.prompt.is_some() {
+            return self.handle_prompt(event);
+        }
+        if let Event::Paste(text) = &event {
+            self.insert_pasted(text);
+            return None;
+        }
         match self.bmode {
             Bmode::Normal => {
                 match EditorCommand::try_from(event) {
-                    Ok(EditorCommand::Resize(size)) =>self.resize(size),
                     Ok(EditorCommand::Up) => self.move_text_location(Direction::Up),
                     Ok(EditorCommand::Down) => self.move_text_location(Direction::Down),
                     Ok(EditorCommand::Left) => self.move_text_location(Direction::Left),
                     Ok(EditorCommand::Right) => self.move_text_location(Direction::Right),
-                    Ok(EditorCommand::Key(c)) => {
-                        match c {
-                            'h' => self.move_text_location(Direction::Left),
-                            'j' => self.move_text_location(Direction::Down),
-                            'k' => self.move_text_location(Direction::Up),
-                            'l' => self.move_text_location(Direction::Right),
-                            'x' => self.delete(),
-                            'X' => self.backspace(),
-                            's' => self.save(),
-                            'i' => self.bmode = Bmode::Insert,
-                            'r' => self.bmode = Bmode::Replace,
-                            'v' => self.start_selection(),
-                            'q' => return true,
-                            'z' => self.center_cursor(),
-                            'p' => self.paste(),
-                            'o' => {self.move_to_end_of_line();self.insert_line();self.bmode = Bmode::Insert;},
-                            'O' => {self.move_up(1); self.insert_line();self.bmode = Bmode::Insert;},
-                            '>' => self.single_indent(),
-                            _ => {},
-                        }
-                    }
+                    Ok(EditorCommand::Control('r')) => self.redo(),
+                    Ok(EditorCommand::Control('w')) if self.pending_keys.is_empty() => self.pending_keys.push(WINDOW_PREFIX),
+                    Ok(EditorCommand::Key(c) | EditorCommand::Control(c)) if self.pending_keys.starts_with(WINDOW_PREFIX) => {
+                        self.pending_keys.clear();
+                        return Some(Request::Window(c));
+                    },
+                    Ok(EditorCommand::Control(c)) if self.pending_keys.is_empty() && SCROLL_KEYS.contains(c) => self.handle_scroll_key(c),
+                    Ok(EditorCommand::Key(c)) if self.is_count_digit(c) => self.push_count_digit(c),
+                    Ok(EditorCommand::Key(c)) if !self.pending_keys.is_empty() => return self.handle_pending_key(c),
+                    Ok(EditorCommand::Key(c)) => return self.handle_normal_key(c),
+                    Ok(EditorCommand::Escape) => {
+                        self.pending_keys.clear();
+                        self.count = None;
+                    },
                     _ => {},
                 }
             },
             Bmode::Insert => {
                 match EditorCommand::try_from(event) {
-                    Ok(EditorCommand::Escape) => self.bmode = Bmode::Normal,
-                    Ok(EditorCommand::Key(c)) => self.insert_char(c),
+                    Ok(EditorCommand::Escape) => {
+                        self.clear_blank_line(self.text_location.line_index);
+                        self.bmode = Bmode::Normal;
+                    },
+                    Ok(EditorCommand::Key(c)) => {self.insert_char(c); self.reindent_typed();},
                     Ok(EditorCommand::Delete) => self.delete(),
-                    Ok(EditorCommand::Backspace) => self.backspace(),
+                    Ok(EditorCommand::Backspace) => self.backspace_softtab(),
                     Ok(EditorCommand::Up) => self.move_text_location(Direction::Up),
                     Ok(EditorCommand::Down) => self.move_text_location(Direction::Down),
                     Ok(EditorCommand::Left) => self.move_text_location(Direction::Left),
                     Ok(EditorCommand::Right) => self.move_text_location(Direction::Right),
-                    Ok(EditorCommand::Tab) => {self.insert_char(' ');self.insert_char(' ');},
+                    Ok(EditorCommand::Tab) => self.insert_tab(),
                     Ok(EditorCommand::Enter) => self.insert_line(),
                     _ => {},
                 }
@@ -111,7 +227,9 @@ impl View {
             },
             Bmode::Visual => {self.needs_redraw = true;
                 match EditorCommand::try_from(event) {
-                    Ok(EditorCommand::Escape) => self.exit_selection(),
+                    Ok(EditorCommand::Escape) => {self.count = None; self.exit_selection();},
+                    Ok(EditorCommand::Key(c)) if self.is_count_digit(c) => self.push_count_digit(c),
+                    Ok(EditorCommand::Control(c)) if SCROLL_KEYS.contains(c) => self.handle_scroll_key(c),
                     Ok(EditorCommand::Up) => self.move_text_location(Direction::Up),
                     Ok(EditorCommand::Down) => self.move_text_location(Direction::Down),
                     Ok(EditorCommand::Left) => self.move_text_location(Direction::Left),
@@ -123,29 +241,454 @@ impl View {
                             'j' => self.move_text_location(Direction::Down),
                             'k' => self.move_text_location(Direction::Up),
                             'l' => self.move_text_location(Direction::Right),
-                            'd' => self.cut(),
-                            'p' => self.paste(),
-                            '>' => self.multi_indent(),
+                            'd' => {self.checkpoint(); self.cut();},
+                            'p' => {self.checkpoint(); self.paste();},
+                            '>' => {self.checkpoint(); self.shift_selection(Direction::Right);},
+                            '<' => {self.checkpoint(); self.shift_selection(Direction::Left);},
                             _ => {},
                         }
                     },
                     _ => {},
                 }
             },
+            Bmode::Command | Bmode::Search => return self.handle_command_line(event),
+        }
+        None
+    }
+
+    /// `Ctrl-e` and `Ctrl-y` scroll a row, `Ctrl-d` and `Ctrl-u` half a
+    /// screen, taking the caret along, and `Ctrl-f` and `Ctrl-b` a screen
+    /// but for two rows kept for context. A count scrolls as many rows, or
+    /// as many screens with `Ctrl-f` and `Ctrl-b`.
+    fn handle_scroll_key(&mut self, c: char) {
+        let count = self.count.take();
+        let page = self.size.height.saturating_sub(2).max(1).saturating_mul(count.unwrap_or(1));
+        match c {
+            'e' => self.scroll_rows(Direction::Down, count.unwrap_or(1)),
+            'y' => self.scroll_rows(Direction::Up, count.unwrap_or(1)),
+            'd' => self.scroll_half_page(Direction::Down, count),
+            'u' => self.scroll_half_page(Direction::Up, count),
+            'f' => self.scroll_rows(Direction::Down, page),
+            'b' => self.scroll_rows(Direction::Up, page),
+            _ => {},
+        }
+    }
+
+    /// Clicking puts the caret where the pointer is, double clicking
+    /// selects the word there and dragging selects from the click on. The
+    /// wheel scrolls the view. `at` is a cell of the screen.
+    pub fn handle_mouse(&mut self, command: EditorCommand) {
+        if self.prompt.is_some() || matches!(self.bmode, Bmode::Command | Bmode::Search) {
+            return;
+        }
+        match command {
+            EditorCommand::Click(at) => {
+                let location = self.location_at(at);
+                let now = Instant::now();
+                let is_double = self.last_click.is_some_and(|(time, last)| last == location && now - time < DOUBLE_CLICK_TIME);
+                self.last_click = (!is_double).then_some((now, location));
+                if self.selection.active {
+                    self.exit_selection();
+                }
+                if is_double {
+                    self.select_word(location);
+                } else {
+                    self.go_to_location(location);
+                }
+            },
+            EditorCommand::Drag(at) => {
+                if !self.selection.active {
+                    self.start_selection();
+                }
+                self.go_to_location(self.location_at(at));
+            },
+            EditorCommand::Scroll(direction, _) => self.scroll_rows(direction, WHEEL_SCROLL_ROWS),
+            _ => return,
+        }
+        self.needs_redraw = true;
+    }
+
+    fn select_word(&mut self, location: Location) {
+        let word = self.buffer.borrow().line(location.line_index).map(|line| line.word_at(location.grapheme_index));
+        let Some(word) = word.filter(|word| !word.is_empty()) else {
+            return;
+        };
+        self.go_to_location(Location { grapheme_index: word.start, ..location });
+        self.start_selection();
+        self.go_to_location(Location { grapheme_index: word.end, ..location });
+    }
+
+    fn handle_normal_key(&mut self, c: char) -> Option<Request> {
+        if c == 'q' && self.recording.is_some() {
+            self.count = None;
+            return Some(Request::Record(None));
+        }
+        if matches!(c, 'g' | 'z' | '>' | '<' | 'q' | '@') {
+            self.pending_keys.push(c);
+            return None;
+        }
+        let count = self.count.take();
+        match c {
+            'h' | 'j' | 'k' | 'l' => {
+                let direction = match c {
+                    'h' => Direction::Left,
+                    'j' => Direction::Down,
+                    'k' => Direction::Up,
+                    _ => Direction::Right,
+                };
+                for _ in 0..count.unwrap_or(1) {
+                    self.move_text_location(direction);
+                }
+            },
+            'G' => {
+                let last_line = self.buffer.borrow().height().saturating_sub(1);
+                self.go_to_line(count.map_or(last_line, |count| count.saturating_sub(1)));
+            },
+            ':' => self.start_command_line(Bmode::Command),
+            '/' => self.start_command_line(Bmode::Search),
+            'n' => self.search_next(),
+            'u' => self.undo(),
+            'x' => {self.checkpoint(); self.delete();},
+            'X' => {self.checkpoint(); self.backspace();},
+            's' => self.save(),
+            'i' => {self.checkpoint(); self.bmode = Bmode::Insert;},
+            'r' => {self.checkpoint(); self.bmode = Bmode::Replace;},
+            'v' => self.start_selection(),
+            'p' => {self.checkpoint(); self.paste();},
+            'o' => {self.checkpoint(); self.apply_motion(Motion::LineEnd);self.insert_line();self.bmode = Bmode::Insert;},
+            'O' => {self.checkpoint(); self.open_line_above();self.bmode = Bmode::Insert;},
+            _ => {},
+        }
+        None
+    }
+
+    /// Called before every change, to be undone as one step.
+    fn checkpoint(&mut self) {
+        if self.buffer.borrow().is_read_only() {
+            self.show_message(Severity::Warning, "Buffer is read-only".to_string());
+        }
+        self.buffer.borrow_mut().checkpoint(self.text_location);
+    }
+
+    fn undo(&mut self) {
+        let location = self.buffer.borrow_mut().undo(self.text_location);
+        if let Some(location) = location {
+            self.go_to_location(location);
+        }
+        self.needs_redraw = true;
+    }
+
+    fn redo(&mut self) {
+        let location = self.buffer.borrow_mut().redo(self.text_location);
+        if let Some(location) = location {
+            self.go_to_location(location);
+        }
+        self.needs_redraw = true;
+    }
+
+    fn handle_prompt(&mut self, event: Event) -> Option<Request> {
+        let answer = match EditorCommand::try_from(event) {
+            Ok(EditorCommand::Escape) => ESCAPE,
+            Ok(EditorCommand::Key(c)) => c.to_ascii_lowercase(),
+            Ok(EditorCommand::Enter) => '\n',
+            _ => return None,
+        };
+        let prompt = self.prompt.take()?;
+        let mut buffer = self.buffer.borrow_mut();
+        match (prompt.kind, answer) {
+            (PromptKind::Recovery { swap_lines }, 'r') => {
+                buffer.replace_lines(&swap_lines);
+                buffer.claim_swap();
+            },
+            (PromptKind::Recovery { .. }, 'o') => buffer.set_read_only(),
+            (PromptKind::Recovery { .. }, 'd') => {
+                buffer.claim_swap();
+                buffer.discard_swap();
+            },
+            (PromptKind::Recovery { .. }, 'a' | ESCAPE) => return Some(Request::Quit),
+            (PromptKind::FileChanged { .. }, 'r') => {
+                drop(buffer);
+                self.reload();
+            },
+            (PromptKind::FileChanged { write_after }, 'k') => {
+                buffer.acknowledge_disk_state();
+                if write_after {
+                    let _ = buffer.save();
+                }
+            },
+            (kind @ PromptKind::FileChanged { .. }, 'd') => {
+                let preview = diff::unified(&buffer.text_lines(), &buffer.disk_lines());
+                self.prompt = Some(Prompt { kind, preview, ..prompt });
+            },
+            (PromptKind::FileChanged { .. }, ESCAPE) | (PromptKind::Info, _) => {},
+            (kind, _) => self.prompt = Some(Prompt { kind, ..prompt }),
+        }
+        self.needs_redraw = true;
+        None
+    }
+
+    /// Shows `lines` in place of the buffer until a key is pressed.
+    pub fn show_info(&mut self, lines: Vec<String>) {
+        self.prompt = Some(Prompt {
+            kind: PromptKind::Info,
+            message: "Press any key to continue".to_string(),
+            preview: lines,
+        });
+        self.needs_redraw = true;
+    }
+
+    fn handle_command_line(&mut self, event: Event) -> Option<Request> {
+        match EditorCommand::try_from(event) {
+            Ok(EditorCommand::Escape) => self.exit_command_line(),
+            Ok(EditorCommand::Key(c)) => self.command_line.push(c),
+            Ok(EditorCommand::Backspace) => {
+                let popped = self.command_line.pop();
+                if popped.is_none() {
+                    self.exit_command_line();
+                }
+            },
+            Ok(EditorCommand::Enter) => return self.execute_command_line(),
+            _ => {},
+        }
+        None
+    }
+
+    /// A leading `0` is a motion of its own rather than the start of a count.
+    const fn is_count_digit(&self, c: char) -> bool {
+        c.is_ascii_digit() && (c != '0' || self.count.is_some())
+    }
+
+    fn push_count_digit(&mut self, c: char) {
+        let digit = c.to_digit(10).map_or(0, |digit| digit as usize);
+        self.count = Some(self.count.unwrap_or(0).saturating_mul(10).saturating_add(digit));
+    }
+
+    /// Carries on with a command of several keys. `>` and `<` shift the
+    /// lines a motion goes over: `>>` the current line, or `count` lines.
+    /// `{count}gt` goes to tab page `count`, `{count}gT` back as many.
+    /// `q` and `@` take the register to record into or play.
+    fn handle_pending_key(&mut self, c: char) -> Option<Request> {
+        self.pending_keys.push(c);
+        let keys = std::mem::take(&mut self.pending_keys);
+        match keys.as_str() {
+            ">g" | "<g" => {
+                self.pending_keys = keys;
+                return None;
+            },
+            "gg" => {
+                let line_index = self.count.take().map_or(0, |count| count.saturating_sub(1));
+                self.go_to_line(line_index);
+            },
+            "gt" | "gT" => {
+                let command = if keys == "gt" { "tabnext" } else { "tabprevious" };
+                let count = self.count.take().map(|count| count.to_string()).unwrap_or_default();
+                return Some(Request::Command(format!("{command} {count}")));
+            },
+            record if record.starts_with('q') => {
+                let register = record.chars().nth(1).filter(|c| c.is_ascii_lowercase() || c.is_ascii_digit());
+                self.count = None;
+                return register.map(|register| Request::Record(Some(register)));
+            },
+            play if play.starts_with('@') => {
+                let register = play.chars().nth(1).filter(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || *c == '@');
+                let count = self.count.take().unwrap_or(1);
+                return register.map(|register| Request::Play { register, count });
+            },
+            "zt" => self.scroll_caret_to(self.scrolloff()),
+            "zz" => self.scroll_caret_to(self.size.height.saturating_sub(1) / 2),
+            "zb" => self.scroll_caret_to(self.size.height.saturating_sub(1).saturating_sub(self.scrolloff())),
+            "gj" => self.move_display_row(Direction::Down),
+            "gk" => self.move_display_row(Direction::Up),
+            _ => {
+                let mut keys = keys.chars();
+                let direction = match keys.next() {
+                    Some('>') => Direction::Right,
+                    Some('<') => Direction::Left,
+                    _ => {
+                        self.count = None;
+                        return None;
+                    },
+                };
+                let motion: String = keys.collect();
+                self.shift_motion(direction, &motion);
+            },
+        }
+        self.count = None;
+        None
+    }
+
+    /// Shifts the lines from the caret's to where `motion` goes, one
+    /// `shiftwidth` each.
+    fn shift_motion(&mut self, direction: Direction, motion: &str) {
+        let count = self.count.take();
+        let current = self.text_location.line_index;
+        let last_line = self.buffer.borrow().height().saturating_sub(1);
+        let lines = match motion {
+            ">" | "<" => current..=current.saturating_add(count.unwrap_or(1) - 1).min(last_line),
+            "j" => current..=current.saturating_add(count.unwrap_or(1)).min(last_line),
+            "k" => current.saturating_sub(count.unwrap_or(1))..=current,
+            "G" => {
+                let target = count.map_or(last_line, |count| count.saturating_sub(1).min(last_line));
+                target.min(current)..=target.max(current)
+            },
+            "gg" => {
+                let target = count.map_or(0, |count| count.saturating_sub(1).min(last_line));
+                target.min(current)..=target.max(current)
+            },
+            "h" | "l" => current..=current,
+            _ => return,
+        };
+        self.checkpoint();
+        let first = *lines.start();
+        for line_index in lines {
+            self.shift_line(line_index, direction);
+        }
+        self.go_to_first_non_blank(first);
+    }
+
+    /// Shifts the selected lines by one `shiftwidth`, or `count` of them,
+    /// leaving the selection in place to shift again.
+    fn shift_selection(&mut self, direction: Direction) {
+        let levels = self.count.take().unwrap_or(1);
+        if let Some((start, end)) = self.process_selection() {
+            for line_index in start.line_index..=end.line_index {
+                for _ in 0..levels {
+                    self.shift_line(line_index, direction);
+                }
+            }
+        }
+        self.snap_to_valid_grapheme();
+    }
+
+    fn go_to_first_non_blank(&mut self, line_index: usize) {
+        self.go_to_line(line_index);
+        self.apply_motion(Motion::FirstNonBlank);
+    }
+
+    fn start_command_line(&mut self, bmode: Bmode) {
+        self.command_line.clear();
+        self.bmode = bmode;
+    }
+
+    fn exit_command_line(&mut self) {
+        self.command_line.clear();
+        self.bmode = Bmode::Normal;
+    }
+
+    /// Runs what was typed on the command line. Anything besides a search or
+    /// a line number is left to the editor.
+    /// Runs `command` as if it was typed on the command line.
+    pub fn run_command_line(&mut self, command: &str) -> Option<Request> {
+        command.clone_into(&mut self.command_line);
+        self.bmode = Bmode::Command;
+        self.execute_command_line()
+    }
+
+    fn execute_command_line(&mut self) -> Option<Request> {
+        let command = std::mem::take(&mut self.command_line);
+        let bmode = std::mem::replace(&mut self.bmode, Bmode::Normal);
+        if let Bmode::Search = bmode {
+            self.last_search = command;
+            self.needs_redraw = true;
+            self.search_next();
+        } else if let Ok(line) = command.trim().parse::<usize>() {
+            self.go_to_line(line.saturating_sub(1));
+        } else if !command.trim().is_empty() {
+            return Some(Request::Command(command));
+        }
+        None
+    }
+
+    fn search_next(&mut self) {
+        let background = self.buffer.borrow().search_in_background(&self.last_search, self.text_location);
+        if let Some(search) = background {
+            // Replacing a search still going on stops it.
+            self.pending_search = Some(search);
+            return;
+        }
+        let found = self.buffer.borrow().search(&self.last_search, self.text_location);
+        self.show_search_result(found);
+    }
+
+    fn show_search_result(&mut self, found: Option<Location>) {
+        if let Some(location) = found {
+            self.go_to_location(location);
+        } else if !self.last_search.is_empty() {
+            self.show_message(Severity::Error, format!("Pattern not found: {}", self.last_search));
+        }
+    }
+
+    fn go_to_line(&mut self, line_index: usize) {
+        self.go_to_location(Location { grapheme_index: 0, line_index });
+    }
+
+    fn go_to_location(&mut self, location: Location) {
+        self.text_location = location;
+        self.snap_to_valid_line();
+        self.snap_to_valid_grapheme();
+        self.scroll_text_location_into_view();
+    }
+
+    /// A bar while text is typed in, so that it sits between graphemes, an
+    /// underline while typing over them, and a block otherwise.
+    pub const fn caret_shape(&self) -> CaretShape {
+        match self.bmode {
+            Bmode::Insert | Bmode::Command | Bmode::Search => CaretShape::Bar,
+            Bmode::Replace => CaretShape::Underline,
+            Bmode::Normal | Bmode::Visual => CaretShape::Block,
         }
-        false
+    }
+
+    /// The command line being typed, if any, starting with the `:` or `/`
+    /// it was opened with.
+    pub fn command_line(&self) -> Option<String> {
+        match self.bmode {
+            Bmode::Command => Some(format!(":{}", self.command_line)),
+            Bmode::Search => Some(format!("/{}", self.command_line)),
+            _ => None,
+        }
+    }
+
+    /// Hands over the messages for the message line given since the last
+    /// call.
+    pub fn take_messages(&mut self) -> Vec<(Severity, String)> {
+        std::mem::take(&mut self.messages)
+    }
+
+    fn show_message(&mut self, severity: Severity, text: String) {
+        self.messages.push((severity, text));
     }
 
     pub fn get_status(&self) -> DocumentStatus {
+        let bmode_string = self.bmode.as_str();
+        let buffer = self.buffer.borrow();
+        let Location { line_index, grapheme_index } = self.text_location;
+        let display_column = buffer.line(line_index).map_or(0, |line| line.width_until(grapheme_index, self.options.tabstop));
+        let count = self.count.map(|count| count.to_string()).unwrap_or_default();
         DocumentStatus {
-            total_lines: self.buffer.lines.len(),
-            current_line_index: self.text_location.line_index,
-            filename: self.buffer.filename.clone(),
-            is_modified: self.buffer.dirty,
-            bmode_string: self.bmode.as_str(),
+            total_lines: buffer.height(),
+            current_line_index: line_index,
+            grapheme_column: grapheme_index,
+            display_column,
+            is_indexing: buffer.is_indexing(),
+            filename: buffer.filename().to_string(),
+            filetype: buffer.filetype().name(),
+            // Files are only ever read as UTF-8.
+            encoding: "utf-8",
+            line_ending: buffer.line_ending().name(),
+            is_modified: buffer.is_dirty(),
+            is_read_only: buffer.is_read_only(),
+            bmode_string,
+            pending_keys: count + &self.pending_keys.replace(WINDOW_PREFIX, "^W"),
+            recording: self.recording,
+            format: self.options.statusline.clone(),
         }
     }
 
+    pub const fn set_recording(&mut self, register: Option<char>) {
+        self.recording = register;
+    }
+
     fn start_selection(&mut self) {
         self.selection.active = true;
         self.bmode = Bmode::Visual;
@@ -157,31 +700,232 @@ impl View {
         self.bmode = Bmode::Normal;
     }
 
-    fn single_indent(&mut self) {
-        let row = self.text_location.line_index;
-        self.indent(row);
+    /// Changes the indent of a non-empty line by one `shiftwidth`, to the
+    /// left or to the right. The new indent is made of tabs if the old one
+    /// had any and of spaces if it had only those, going by `expandtab`
+    /// for lines not indented yet.
+    fn shift_line(&mut self, line_index: usize, direction: Direction) {
+        let is_empty = self.buffer.borrow().line(line_index).is_none_or(|line| line.grapheme_count() == 0);
+        let Some((count, width, use_tabs)) = self.indent_of(line_index).filter(|_| !is_empty) else {
+            return;
+        };
+        let width = match direction {
+            Direction::Left => width.saturating_sub(self.options.shiftwidth()),
+            _ => width + self.options.shiftwidth(),
+        };
+        let text = self.indent_text(0, width, use_tabs);
+        self.replace_indent(line_index, count, &text);
     }
 
-    fn indent(&mut self, row: usize) {
-        let oldcol = self.text_location.grapheme_index;
-        let oldrow = self.text_location.line_index;
-        self.text_location.grapheme_index = 0;
-        self.text_location.line_index = row;
-        self.insert_char(' ');
-        self.insert_char(' ');
-        self.text_location.grapheme_index = oldcol;
-        self.text_location.line_index = oldrow;
+    /// How many graphemes of indent a line has, how wide they are, and
+    /// whether more indent should be made of tabs: if the indent has any,
+    /// and going by `expandtab` when there is none.
+    fn indent_of(&self, line_index: usize) -> Option<(usize, usize, bool)> {
+        let buffer = self.buffer.borrow();
+        let line = buffer.line(line_index)?;
+        let (count, width) = line.indent(self.options.tabstop);
+        let indent = line.text(0..count);
+        let use_tabs = if indent.is_empty() { !self.options.expandtab } else { indent.contains('\t') };
+        Some((count, width, use_tabs))
     }
 
-    fn multi_indent(&mut self) {
-        let col = self.text_location.grapheme_index;
-        let row = self.text_location.line_index;
-        if let Some((start, end)) = self.process_selection() {
-        for i in start.line_index..=end.line_index {
-            self.indent(i);
+    /// Puts `text` in place of the first `count` graphemes of a line,
+    /// keeping the caret on the same character.
+    fn replace_indent(&mut self, line_index: usize, count: usize, text: &str) {
+        let mut buffer = self.buffer.borrow_mut();
+        if buffer.is_read_only() {
+            return;
         }
+        buffer.delete_line(line_index, 0, count);
+        buffer.insert_text(text, Location { line_index, grapheme_index: 0 });
+        drop(buffer);
+        if self.text_location.line_index == line_index {
+            self.text_location.grapheme_index = self.text_location.grapheme_index.saturating_sub(count) + text.graphemes(true).count();
+        }
+        self.scroll_text_location_into_view();
+        self.needs_redraw = true;
+    }
+
+    fn indent_rules(&self) -> &'static IndentRules {
+        self.buffer.borrow().filetype().indent_rules()
+    }
+
+    /// The indent width a line at `line_index` gets from the nearest
+    /// non-blank line above it, and whether to make it of tabs.
+    fn indent_after(&self, line_index: usize) -> (usize, bool) {
+        let rules = self.indent_rules();
+        let buffer = self.buffer.borrow();
+        let above = (0..line_index)
+            .rev()
+            .find(|index| buffer.line(*index).is_some_and(|line| !line.to_string().trim().is_empty()));
+        drop(buffer);
+        let Some(above) = above else {
+            return (0, !self.options.expandtab);
+        };
+        let Some((_, width, use_tabs)) = self.indent_of(above) else {
+            return (0, !self.options.expandtab);
+        };
+        let opens = self.options.smartindent
+            && self.buffer.borrow().line(above).is_some_and(|line| rules.opens_block(&line.to_string()));
+        (if opens { width + self.options.shiftwidth() } else { width }, use_tabs)
+    }
+
+    /// Indents the caret's line, just opened below another: as deep as the
+    /// line above, one level deeper after a line opening a block, and one
+    /// level less if it starts by closing one.
+    fn autoindent(&mut self) {
+        let line_index = self.text_location.line_index;
+        let (width, use_tabs) = self.indent_after(line_index);
+        let Some((count, _, _)) = self.indent_of(line_index) else {
+            return;
+        };
+        let closes = self.options.smartindent
+            && self
+                .buffer
+                .borrow()
+                .line(line_index)
+                .is_some_and(|line| self.indent_rules().closes_block(&line.text(count..line.grapheme_count())));
+        let width = if closes { width.saturating_sub(self.options.shiftwidth()) } else { width };
+        let text = self.indent_text(0, width, use_tabs);
+        self.replace_indent(line_index, count, &text);
+    }
+
+    /// After a character is typed in Insert mode, moves a line that now
+    /// starts by closing a block one level out, and back in if the
+    /// character turned the closer into something else, as `fi` into
+    /// `find`. Lines whose indent was changed by hand are left alone.
+    fn reindent_typed(&mut self) {
+        if !(self.options.autoindent && self.options.smartindent) {
+            return;
+        }
+        let line_index = self.text_location.line_index;
+        let Some((count, width, _)) = self.indent_of(line_index) else {
+            return;
+        };
+        let typed = self
+            .buffer
+            .borrow()
+            .line(line_index)
+            .map(|line| line.text(count..self.text_location.grapheme_index))
+            .unwrap_or_default();
+        let mut before = typed.clone();
+        if before.pop().is_none() {
+            return;
+        }
+        let rules = self.indent_rules();
+        let (inner, use_tabs) = self.indent_after(line_index);
+        let outer = inner.saturating_sub(self.options.shiftwidth());
+        let target = match (rules.closes_block(&before), rules.closes_block(&typed)) {
+            (false, true) if width == inner => outer,
+            (true, false) if width == outer => inner,
+            _ => return,
+        };
+        let text = self.indent_text(0, target, use_tabs);
+        self.replace_indent(line_index, count, &text);
+    }
+
+    /// Takes the indent off a line with nothing else on it, so that
+    /// autoindent left unused does not stay behind as trailing whitespace.
+    fn clear_blank_line(&mut self, line_index: usize) {
+        if !self.options.autoindent {
+            return;
+        }
+        let blank = self
+            .buffer
+            .borrow()
+            .line(line_index)
+            .filter(|line| line.grapheme_count() > 0 && line.to_string().trim().is_empty())
+            .map(Line::grapheme_count);
+        if let Some(count) = blank {
+            self.replace_indent(line_index, count, "");
+        }
+    }
+
+    /// Whitespace spanning the display columns from `from` to `to`: tabs as
+    /// far as they reach if `use_tabs`, then spaces.
+    fn indent_text(&self, from: usize, to: usize, use_tabs: bool) -> String {
+        let tabstop = self.options.tabstop;
+        let mut text = String::new();
+        let mut column = from;
+        if use_tabs {
+            while column - column % tabstop + tabstop <= to {
+                text.push('\t');
+                column = column - column % tabstop + tabstop;
+            }
+        }
+        text.push_str(&" ".repeat(to.saturating_sub(column)));
+        text
+    }
+
+    /// The grapheme index and display column where the spaces and tabs
+    /// right before the caret begin.
+    fn whitespace_before_caret(&self) -> (usize, usize) {
+        let buffer = self.buffer.borrow();
+        let Some(line) = buffer.line(self.text_location.line_index) else {
+            return (0, 0);
+        };
+        let grapheme_index = self.text_location.grapheme_index;
+        let run = line
+            .text(0..grapheme_index)
+            .chars()
+            .rev()
+            .take_while(|c| *c == ' ' || *c == '\t')
+            .count();
+        let start = grapheme_index - run;
+        (start, line.width_until(start, self.options.tabstop))
+    }
+
+    /// The Tab key in Insert mode. Moves on to the next `softtabstop`, or
+    /// tabstop, filling the gap with spaces or, without `expandtab`, with as
+    /// many tab characters as fit.
+    fn insert_tab(&mut self) {
+        let Options { tabstop, softtabstop, expandtab, .. } = self.options;
+        if softtabstop == 0 && !expandtab {
+            self.insert_char('\t');
+            return;
+        }
+        let stop = if softtabstop == 0 { tabstop } else { softtabstop };
+        let column = self.text_location_to_position().col;
+        let target = (column / stop + 1) * stop;
+        let (start, start_column) = if expandtab {
+            (self.text_location.grapheme_index, column)
+        } else {
+            self.whitespace_before_caret()
+        };
+        let text = self.indent_text(start_column, target, !expandtab);
+        let line_index = self.text_location.line_index;
+        let mut buffer = self.buffer.borrow_mut();
+        if buffer.is_read_only() {
+            return;
+        }
+        buffer.delete_line(line_index, start, self.text_location.grapheme_index);
+        buffer.insert_text(&text, Location { line_index, grapheme_index: start });
+        drop(buffer);
+        self.text_location.grapheme_index = start + text.len();
+        self.scroll_text_location_into_view();
+        self.needs_redraw = true;
+    }
+
+    /// Backspace in Insert mode. Over spaces, goes back to the previous
+    /// `softtabstop` in one go.
+    fn backspace_softtab(&mut self) {
+        let softtabstop = self.options.softtabstop;
+        let column = self.text_location_to_position().col;
+        let (start, start_column) = self.whitespace_before_caret();
+        let spaces_only = self
+            .buffer
+            .borrow()
+            .line(self.text_location.line_index)
+            .is_some_and(|line| !line.text(start..self.text_location.grapheme_index).contains('\t'));
+        let count = if softtabstop > 0 && column > 0 && spaces_only {
+            let target = ((column - 1) / softtabstop * softtabstop).max(start_column);
+            column - target
+        } else {
+            1
+        };
+        for _ in 0..count.max(1) {
+            self.backspace();
         }
-        self.exit_selection();
     }
 
     fn process_selection(&self) -> Option<(Location, Location)> {
@@ -198,27 +942,43 @@ impl View {
         }
     }
 
-    fn is_selected(&self, line_index: usize, grapheme_index: usize) -> bool {
-        if let Some((start, end)) = self.process_selection() {
-            (line_index, grapheme_index) >= (start.line_index, start.grapheme_index)
-                && (line_index, grapheme_index) < (end.line_index, end.grapheme_index)
-        } else {
-            false
+    /// The display columns of `line` covered by the selection, if any.
+    fn selected_columns(&self, line: &Line, line_index: usize) -> Option<Range<usize>> {
+        let (start, end) = self.process_selection()?;
+        if line_index < start.line_index || line_index > end.line_index {
+            return None;
         }
+        let first = if line_index == start.line_index { start.grapheme_index } else { 0 };
+        let last = if line_index == end.line_index { end.grapheme_index } else { line.grapheme_count() };
+        Some(line.width_until(first, self.options.tabstop)..line.width_until(last, self.options.tabstop))
+    }
+
+    /// The display columns of the matches of the last search in `line`.
+    fn search_matches(&self, line: &Line) -> Vec<Range<usize>> {
+        let length = self.last_search.graphemes(true).count();
+        let tabstop = self.options.tabstop;
+        let mut matches = Vec::new();
+        let mut after = None;
+        while let Some(start) = line.find(&self.last_search, after) {
+            matches.push(line.width_until(start, tabstop)..line.width_until(start + length, tabstop));
+            after = Some(start + length.max(1) - 1);
+        }
+        matches
     }
 
     fn selected_text(&self) -> Option<String> {
         let (start, end) = self.process_selection()?;
 
         let mut out = String::new();
+        let buffer = self.buffer.borrow();
 
         for row in start.line_index..=end.line_index {
-            let line = self.buffer.lines.get(row)?;
+            let line = buffer.line(row)?;
 
             let g_start = if row == start.line_index { start.grapheme_index } else { 0 };
-            let g_end   = if row == end.line_index   { end.grapheme_index }   else { self.buffer.lines.get(row).map_or(0, Line::grapheme_count) };
+            let g_end   = if row == end.line_index   { end.grapheme_index }   else { line.grapheme_count() };
 
-            out.push_str(&line.get_visible_graphemes(g_start..g_end));
+            out.push_str(&line.text(g_start..g_end));
 
             if row != end.line_index {
                 out.push('\n');
@@ -229,12 +989,8 @@ impl View {
     }
 
     fn copy(&mut self) {
-        let sel = self.selected_text();
-        match sel {
-            Some(text) => {
-                self.clipboard = text.clone();
-            },
-            None => {}
+        if let Some(text) = self.selected_text() {
+            *self.clipboard.borrow_mut() = text;
         }
     }
 
@@ -249,78 +1005,67 @@ impl View {
     }
 
     fn delete_selection(&mut self) {
-        let (start, end) = match self.process_selection() {
-            Some (r) => r,
-            None => return,
+        let Some((start, end)) = self.process_selection() else {
+            return;
         };
-
-        if start.line_index == end.line_index {
-            self.buffer.delete_line(start.line_index, start.grapheme_index, end.grapheme_index);
-        } else {
-            self.buffer.delete_line(end.line_index, 0, end.grapheme_index);
-            let tail = Line::from(&self.buffer.lines[end.line_index].get_visible_graphemes(0..self.buffer.lines[end.line_index].grapheme_count()));
-            for _ in start.line_index+1..=end.line_index {
-                self.buffer.lines.remove(start.line_index+1);
-            }
-            self.buffer.delete_line(start.line_index, start.grapheme_index, self.buffer.lines[start.line_index].grapheme_count());
-            self.buffer.lines[start.line_index].append(&tail);
+        let mut buffer = self.buffer.borrow_mut();
+        if buffer.is_read_only() {
+            return;
         }
+
+        buffer.delete_range(start, end);
+        drop(buffer);
+        self.text_location = start;
         self.exit_selection();
     }
 
-    fn insert_text(&mut self, text: String) {
+    /// Inserts `text` at the caret, over the selection if there is one,
+    /// and puts the caret after it.
+    fn insert_text(&mut self, text: &str) {
+        if self.buffer.borrow().is_read_only() {
+            return;
+        }
         if self.selection.active {
             self.delete_selection();
         }
+        let end = self.buffer.borrow_mut().insert_text(text, self.text_location);
+        self.go_to_location(end);
+        self.needs_redraw = true;
+    }
 
-        let row = self.text_location.line_index;
-        let col = self.text_location.grapheme_index;
-
-        let mut lines = text.lines();
-
-        if let Some(first) = lines.next() {
-            let tail = Line::from(&self.buffer.lines[row].get_visible_graphemes(col..self.buffer.lines[row].grapheme_count()));
-            self.buffer.delete_line(row, col, self.buffer.lines[row].grapheme_count());
-            self.buffer.insert_text(String::from(first), Location{line_index: row, grapheme_index: col});
-
-            let mut insert_row = row + 1;
-
-            for l in lines {
-                self.buffer.insert_line(Location {line_index: insert_row, grapheme_index: 0});
-                self.buffer.insert_text(String::from(l), Location{line_index: insert_row, grapheme_index: 0});
-            }
-
-            self.buffer.lines[insert_row -1].append(&tail);
-            self.text_location = Location {
-                line_index: insert_row -1, 
-                grapheme_index: col,
-            }
+    /// Text pasted into the terminal arrives all at once rather than as
+    /// keys, so it is inserted as it is, without being indented, and can be
+    /// undone in one go.
+    fn insert_pasted(&mut self, text: &str) {
+        if matches!(self.bmode, Bmode::Command | Bmode::Search) {
+            self.command_line.extend(text.chars().filter(|c| !matches!(c, '\n' | '\r')));
+            return;
+        }
+        self.checkpoint();
+        self.insert_text(text);
+        if self.selection.active {
+            self.exit_selection();
         }
-        self.needs_redraw = true;
     }
 
     fn paste(&mut self) {
-        let text = self.clipboard.clone();
+        let text = self.clipboard.borrow().clone();
 
-        self.insert_text(text);
+        self.insert_text(&text);
         self.exit_selection();
     }
 
-    fn center_cursor(&mut self) {
-        let Size { height, .. } = self.size;
-        if self.text_location.line_index.saturating_sub(self.scroll_offset.row) < height/2 {
-            self.scroll_vertically(self.text_location.line_index.saturating_sub(height/2));
-        } else {
-            self.scroll_vertically(self.text_location.line_index.saturating_add(height/2));
-        }
-    }
-
     fn insert_char(&mut self, c: char) {
-        let old_len = self.buffer.lines.get(self.text_location.line_index)
+        let mut buffer = self.buffer.borrow_mut();
+        if buffer.is_read_only() {
+            return;
+        }
+        let old_len = buffer.line(self.text_location.line_index)
             .map_or(0, Line::grapheme_count);
-        self.buffer.insert_char(c, self.text_location);
-        let len = self.buffer.lines.get(self.text_location.line_index)
+        buffer.insert_char(c, self.text_location);
+        let len = buffer.line(self.text_location.line_index)
             .map_or(0, Line::grapheme_count);
+        drop(buffer);
         if len-old_len > 0 {
             self.move_text_location(Direction::Right);
         }
@@ -328,106 +1073,334 @@ impl View {
     }
 
     fn insert_line(&mut self) {
-        self.buffer.insert_line(self.text_location);
+        let mut buffer = self.buffer.borrow_mut();
+        if buffer.is_read_only() {
+            return;
+        }
+        buffer.insert_line(self.text_location);
+        drop(buffer);
         self.move_text_location(Direction::Down);
-        self.move_to_start_of_line();
+        self.apply_motion(Motion::LineStart);
+        if self.options.autoindent {
+            self.clear_blank_line(self.text_location.line_index.saturating_sub(1));
+            self.autoindent();
+        }
+        self.needs_redraw = true;
+    }
+
+    /// `O`: a new line above the caret's, indented as if opened below the
+    /// line before it.
+    fn open_line_above(&mut self) {
+        let line_index = self.text_location.line_index;
+        let mut buffer = self.buffer.borrow_mut();
+        if buffer.is_read_only() {
+            return;
+        }
+        buffer.insert_line(Location { line_index, grapheme_index: 0 });
+        drop(buffer);
+        self.go_to_line(line_index);
+        if self.options.autoindent {
+            self.autoindent();
+        }
         self.needs_redraw = true;
     }
 
     fn delete(&mut self) {
-        self.buffer.delete(self.text_location);
+        let mut buffer = self.buffer.borrow_mut();
+        if buffer.is_read_only() {
+            return;
+        }
+        buffer.delete(self.text_location);
+        drop(buffer);
         self.needs_redraw = true;
     }
 
     fn backspace(&mut self) {
         if self.text_location.line_index != 0 || self.text_location.grapheme_index != 0{
             if self.text_location.grapheme_index == 0 {
-                self.move_up(1);
-                self.move_to_end_of_line();
+                self.apply_motion(Motion::Up(1));
+                self.apply_motion(Motion::LineEnd);
             } else {
-                self.move_left();
+                self.apply_motion(Motion::Left);
             }
             self.delete();
         }
     }
 
-    pub fn load(&mut self, filename: &str) {
-        if let Ok(buffer) = Buffer::load(filename) {
-            self.buffer = buffer;
+    pub fn buffer(&self) -> Rc<RefCell<Buffer>> {
+        Rc::clone(&self.buffer)
+    }
+
+    pub const fn position(&self) -> Position {
+        Position {
+            text_location: self.text_location,
+            scroll_offset: self.scroll_offset,
+        }
+    }
+
+    /// Applies a `:set` argument to this view's options.
+    pub fn set_option(&mut self, argument: &str) -> Result<(), String> {
+        self.options.set(argument)?;
+        self.top_subrow = 0;
+        self.scroll_text_location_into_view();
+        self.needs_redraw = true;
+        Ok(())
+    }
+
+    fn gutter(&self) -> Gutter {
+        Gutter::new(&self.options, self.buffer.borrow().height(), self.text_location.line_index)
+    }
+
+    /// How many columns are left for text beside the gutter.
+    fn text_width(&self) -> usize {
+        self.size.width.saturating_sub(self.gutter().width())
+    }
+
+    /// Another view onto the same buffer at the same position, for a new
+    /// window. The clipboard stays shared between the two.
+    pub fn split_off(&self) -> Self {
+        Self {
+            buffer: Rc::clone(&self.buffer),
+            clipboard: Rc::clone(&self.clipboard),
+            text_location: self.text_location,
+            scroll_offset: self.scroll_offset,
+            last_search: self.last_search.clone(),
+            options: self.options.clone(),
+            recording: self.recording,
+            ..Self::default()
+        }
+    }
+
+    /// Switches the view over to `buffer`, restoring `position` in it. The
+    /// first time a buffer is shown, a swap file left behind for it is
+    /// looked for.
+    pub fn show_buffer(&mut self, buffer: Rc<RefCell<Buffer>>, position: Position) {
+        self.buffer = buffer;
+        self.text_location = position.text_location;
+        self.scroll_offset = position.scroll_offset;
+        self.selection.active = false;
+        self.bmode = Bmode::Normal;
+        self.snap_to_valid_line();
+        self.snap_to_valid_grapheme();
+        self.scroll_text_location_into_view();
+        let needs_swap_check = self.buffer.borrow().needs_swap_check();
+        if needs_swap_check {
+            self.check_swap();
+        } else {
+            self.check_disk_changes();
         }
         self.needs_redraw = true;
     }
 
+    fn check_swap(&mut self) {
+        let filename = self.buffer.borrow().filename().to_string();
+        match Swap::read_existing(&filename) {
+            Some(swap_text) => self.prompt_recovery(&filename, &swap_text),
+            None => self.buffer.borrow_mut().claim_swap(),
+        }
+    }
+
+    fn prompt_recovery(&mut self, filename: &str, swap_text: &str) {
+        let swap_lines: Vec<String> = swap_text.lines().map(String::from).collect();
+        let mut preview = diff::unified(&self.buffer.borrow().text_lines(), &swap_lines);
+        if preview.is_empty() {
+            preview.push("(swap file matches the file on disk)".to_string());
+        }
+        let swap_path = Swap::path_for(filename);
+        self.prompt = Some(Prompt {
+            kind: PromptKind::Recovery { swap_lines },
+            message: format!(
+                "Found swap file {}: [r]ecover, [o]pen read-only, [d]elete it, [a]bort",
+                swap_path.display()
+            ),
+            preview,
+        });
+    }
+
     pub fn save(&mut self) {
-        let _ = self.buffer.save();
+        // A file deleted behind our back is simply written anew.
+        let change = self.buffer.borrow_mut().disk_change();
+        if change == DiskChange::Modified {
+            self.prompt_file_changed(true);
+        } else {
+            let result = self.buffer.borrow_mut().save();
+            let buffer = self.buffer.borrow();
+            let message = match result {
+                Ok(()) => (Severity::Info, format!("\"{}\" {}L written", buffer.filename(), buffer.height())),
+                Err(err) => (Severity::Error, format!("Cannot write \"{}\": {err}", buffer.filename())),
+            };
+            drop(buffer);
+            self.messages.push(message);
+        }
+    }
+
+    /// Called when the terminal regains focus, which is when other programs
+    /// have most likely touched the file. A clean buffer is simply reloaded.
+    pub fn check_disk_changes(&mut self) {
+        if self.prompt.is_some() {
+            return;
+        }
+        let change = self.buffer.borrow_mut().disk_change();
+        match change {
+            DiskChange::Unchanged => return,
+            DiskChange::Deleted => {
+                let mut buffer = self.buffer.borrow_mut();
+                buffer.acknowledge_disk_state();
+                let message = format!("\"{}\" was deleted on disk", buffer.filename());
+                drop(buffer);
+                self.show_message(Severity::Warning, message);
+                self.needs_redraw = true;
+                return;
+            },
+            DiskChange::Modified => {},
+        }
+        let dirty = self.buffer.borrow().is_dirty();
+        if dirty {
+            self.prompt_file_changed(false);
+        } else {
+            self.reload();
+        }
     }
 
-    fn render_line(row: usize, line_text: &str) {
-        Self::complex_render(row, Some(line_text), None, None);
+    fn prompt_file_changed(&mut self, write_after: bool) {
+        self.prompt = Some(Prompt {
+            kind: PromptKind::FileChanged { write_after },
+            message: format!("{} changed on disk: [r]eload, [k]eep ours, [d]iff", self.buffer.borrow().filename()),
+            preview: Vec::new(),
+        });
+        self.needs_redraw = true;
     }
 
-    fn complex_render(row: usize, s1: Option<&str>, s2: Option<&str>, s3: Option<&str>) {
-        let result = Terminal::complex_print(row, s1, s2, s3);
-        debug_assert!(result.is_ok(), "Failed to render line");
+    /// Replaces the buffer's content with what is on disk. The buffer is
+    /// replaced in place, so every view showing it sees the new content.
+    fn reload(&mut self) {
+        let reloaded = self.buffer.borrow_mut().reload();
+        if reloaded {
+            self.snap_to_valid_line();
+            self.snap_to_valid_grapheme();
+            self.scroll_text_location_into_view();
+        }
+        self.needs_redraw = true;
+    }
+
+    fn render_line(&self, row: usize, line_text: &str) {
+        self.render_styled_line(row, &StyledLine::plain(line_text));
+    }
+
+    /// Draws one of the `~` rows marking that the buffer ended above.
+    fn render_filler(&self, row: usize) {
+        self.render_styled_line(row, &StyledLine::new("~", Group::NonText.style()));
+    }
+
+    fn render_styled_line(&self, row: usize, line: &StyledLine) {
+        let at = Coords { row: self.origin.row.saturating_add(row), col: self.origin.col };
+        Terminal::print_line(at, self.size.width, line);
     }
 
     fn render_buffer(&self) {
-        let Size {height, width} = self.size;
+        if self.options.wrap {
+            self.render_wrapped_buffer();
+            return;
+        }
+        let Size {height, ..} = self.size;
+        let gutter = self.gutter();
+        let width = self.text_width();
+        let buffer = self.buffer.borrow();
         for row in 0..height {
-            if let Some(e) = self.buffer.lines.get(row.saturating_add(self.scroll_offset.row)) {
-                let xbound1 = self.scroll_offset.col;
-                let xbound2 = self.scroll_offset.col + width;
-                let mut firstselec = None;
-                let mut lastselec = None;
-                for col in xbound1..xbound2 {
-                    if self.is_selected(row,col) {
-                        if firstselec.is_none() {
-                            firstselec = Some(col-xbound1);
-                        }
-                        lastselec = Some(col-xbound1+1);
-                    }
-                }
-                let (left, mid, right) = match firstselec {
-                    None => (Some(e.get_visible_graphemes(xbound1..xbound2)), None, None),
-                    Some(a) => {
-                        match lastselec {
-                            Some(b) => {
-                            let left = e.get_visible_graphemes(xbound1..a);
-                            let mid = e.get_visible_graphemes(a..b);
-                            let right = e.get_visible_graphemes(b..xbound2);
-                            (
-                                if left.is_empty() { None } else { Some(left) },
-                                if mid.is_empty()  { None } else { Some(mid) },
-                                if right.is_empty(){ None } else { Some(right) },
-                            )
-                            },
-                            _ => (None,None,None) // never happens
-                        }
-                    }
-                };
-                Self::complex_render(row, left.as_deref(), mid.as_deref(), right.as_deref());
+            let line_index = row.saturating_add(self.scroll_offset.row);
+            if let Some(line) = buffer.line(line_index) {
+                let columns = self.scroll_offset.col..self.scroll_offset.col + width;
+                self.render_text_row(row, line, line_index, columns, buffer.tokens(line_index), &gutter.text(line_index));
             } else  {
-                Self::render_line(row, "~");
+                self.render_filler(row);
             }
         }
     }
 
+    /// Draws the buffer with each line flowing over as many rows as it
+    /// needs, starting `top_subrow` rows into the first line.
+    fn render_wrapped_buffer(&self) {
+        let Size {height, ..} = self.size;
+        let gutter = self.gutter();
+        let continuation = " ".repeat(gutter.width()) + &self.options.showbreak;
+        let buffer = self.buffer.borrow();
+        let mut line_index = self.scroll_offset.row;
+        let mut subrow = self.top_subrow;
+        let mut row = 0;
+        while row < height {
+            let Some(line) = buffer.line(line_index) else {
+                self.render_filler(row);
+                row += 1;
+                continue;
+            };
+            let starts = self.wrap_starts(line);
+            for (index, start) in starts.iter().enumerate().skip(subrow) {
+                if row == height {
+                    break;
+                }
+                let end = starts.get(index + 1).copied().unwrap_or_else(|| line.grapheme_count());
+                let columns = line.width_until(*start, self.options.tabstop)..line.width_until(end, self.options.tabstop);
+                let prefix = if index == 0 { gutter.text(line_index) } else { continuation.clone() };
+                self.render_text_row(row, line, line_index, columns, buffer.tokens(line_index), &prefix);
+                row += 1;
+            }
+            subrow = 0;
+            line_index += 1;
+        }
+    }
+
+    /// Draws the display columns `columns` of `line` on `row` after `prefix`,
+    /// colored by its tokens, with the part that is selected highlighted.
+    fn render_text_row(&self, row: usize, line: &Line, line_index: usize, columns: Range<usize>, tokens: &[Token], prefix: &str) {
+        let tabstop = self.options.tabstop;
+        let mut styled = StyledLine::default();
+        styled.push(prefix, Group::LineNr.style());
+        let text_start = styled.width();
+        styled.push(&line.get_visible_graphemes(columns.clone(), tabstop), Style::default());
+        let offset = |column: usize| column.saturating_sub(columns.start) + text_start;
+        let to_row = |range: Range<usize>| offset(range.start.max(columns.start))..offset(range.end.min(columns.end));
+        for token in tokens {
+            let start = line.width_until(token.range.start, tabstop);
+            let end = line.width_until(token.range.end, tabstop);
+            styled.highlight(to_row(start..end), token.kind.group().style(), SYNTAX_PRIORITY);
+        }
+        for found in self.search_matches(line) {
+            styled.highlight(to_row(found), Group::Search.style(), SEARCH_PRIORITY);
+        }
+        if let Some(selected) = self.selected_columns(line, line_index) {
+            styled.highlight(to_row(selected), Group::Visual.style(), SELECTION_PRIORITY);
+        }
+        self.render_styled_line(row, &styled);
+    }
+
+    fn render_preview(&self, prompt: &Prompt) {
+        let Size { height, width } = self.size;
+        for row in 0..height {
+            let text = prompt.preview.get(row).map_or_else(|| "~".to_string(), |line| {
+                Line::from(line).get_visible_graphemes(0..width, self.options.tabstop)
+            });
+            self.render_line(row, &text);
+        }
+    }
+
+    fn render_prompt_message(&self, prompt: &Prompt) {
+        let Size { height, width } = self.size;
+        let message = Line::from(&prompt.message).get_visible_graphemes(0..width, self.options.tabstop);
+        self.render_line(height.saturating_sub(1), &message);
+    }
+
     fn render_welcome_screen(&self) {
         let Size {height, ..} = self.size;
         for row in 0..height {
-            let _ = Terminal::clear_line();
             if row == height / 2 {
-                self.draw_welcome_message();
+                self.draw_welcome_message(row);
             } else  {
-                Self::render_line(row, "~");
-            }
-            if row.saturating_add(1) < height {
-                let _ = Terminal::move_caret_to(Coords {row: row+1, col: 0} );
+                self.render_filler(row);
             }
         }
     }
 
-    fn draw_welcome_message(&self) {
+    fn draw_welcome_message(&self, row: usize) {
         let mut welcome_msg = format!("{NAME} version {VERSION}");
         let width: usize = self.size.width;
         let len = welcome_msg.len(); 
@@ -436,31 +1409,19 @@ impl View {
         let spaces = " ".repeat(padding.saturating_sub(1));
         welcome_msg = format!("~{spaces}{welcome_msg}");
         welcome_msg.truncate(width);
-        let _ = Terminal::print(Some(&welcome_msg), None, None);
+        self.render_line(row, &welcome_msg);
     }
 
-    fn resize(&mut self, size: Size){
+    /// Moves the view to `origin` on the screen and gives it `size` cells.
+    pub fn resize(&mut self, origin: Coords, size: Size){
+        self.origin = origin;
         self.size = size;
         self.scroll_text_location_into_view();
         self.needs_redraw = true;                
     }
 
-    fn scroll_vertically(&mut self, to: usize) {
-        let Size { height, .. } = self.size;
-        let offset_changed = if to < self.scroll_offset.row {
-            self.scroll_offset.row = to;
-            true
-        } else if to >= self.scroll_offset.row.saturating_add(height) {
-            self.scroll_offset.row = to.saturating_sub(height).saturating_add(1);
-            true
-        } else {
-            false
-        };
-        self.needs_redraw = self.needs_redraw || offset_changed;
-    }
-
     fn scroll_horizontally(&mut self, to: usize) {
-        let Size { width, .. } = self.size;
+        let width = self.text_width();
         let offset_changed = if to < self.scroll_offset.col {
             self.scroll_offset.col = to;
             true
@@ -474,14 +1435,226 @@ impl View {
     }
 
     fn scroll_text_location_into_view(&mut self) {
-        let Coords { row, col } = self.text_location_to_position();
-        self.scroll_vertically(row);
-        self.scroll_horizontally(col);
+        self.scroll_caret_row_into_view();
+        if !self.options.wrap {
+            self.scroll_horizontally(self.text_location_to_position().col);
+        } else if self.scroll_offset.col != 0 {
+            self.scroll_offset.col = 0;
+            self.needs_redraw = true;
+        }
+    }
+
+    /// Scrolls by display rows so that the row with the caret is on screen,
+    /// with `scrolloff` rows above and below it where the buffer has them.
+    fn scroll_caret_row_into_view(&mut self) {
+        let (caret, _) = self.caret_row();
+        let margin = self.scrolloff();
+        let top = self.top();
+        let highest_top = self.rows_up(caret, margin);
+        let lowest_top = self.rows_up(self.rows_down(caret, margin), self.size.height.saturating_sub(1));
+        self.set_top(if highest_top < top { highest_top } else { top.max(lowest_top) });
+    }
+
+    /// The `scrolloff` option, cut down to what fits on the view.
+    fn scrolloff(&self) -> usize {
+        self.options.scrolloff.min(self.size.height.saturating_sub(1) / 2)
+    }
+
+    /// The first display row on screen, as line index and row within the
+    /// line. Without `wrap`, every line is a single row.
+    const fn top(&self) -> (usize, usize) {
+        (self.scroll_offset.row, self.top_subrow)
+    }
+
+    fn set_top(&mut self, top: (usize, usize)) {
+        if top != self.top() {
+            (self.scroll_offset.row, self.top_subrow) = top;
+            self.needs_redraw = true;
+        }
+    }
+
+    /// The display row of the caret, as line index and row within the line,
+    /// and its column on that row.
+    fn caret_row(&self) -> ((usize, usize), usize) {
+        let Coords { row, col } = self.display_position(self.text_location);
+        ((self.text_location.line_index, row), col)
+    }
+
+    /// Moves the caret to the display row `to`, as close to column `col` as
+    /// the row allows.
+    fn move_caret_to_row(&mut self, to: (usize, usize), col: usize) {
+        let (line_index, row) = to;
+        self.buffer.borrow_mut().ensure_loaded(line_index..line_index.saturating_add(1));
+        self.text_location = Location { line_index, grapheme_index: self.grapheme_on_row(line_index, row, col) };
+    }
+
+    /// The display row `count` rows above the one at `from`, both given as
+    /// line index and row within the line.
+    fn rows_up(&self, from: (usize, usize), count: usize) -> (usize, usize) {
+        let (mut line_index, mut subrow) = from;
+        for _ in 0..count {
+            if subrow > 0 {
+                subrow -= 1;
+            } else if line_index > 0 {
+                line_index -= 1;
+                subrow = self.row_count(line_index).saturating_sub(1);
+            } else {
+                break;
+            }
+        }
+        (line_index, subrow)
+    }
+
+    /// The display row `count` rows below the one at `from`, stopping at the
+    /// last row of the buffer.
+    fn rows_down(&self, from: (usize, usize), count: usize) -> (usize, usize) {
+        let last_line = self.buffer.borrow().height().saturating_sub(1);
+        let (mut line_index, mut subrow) = from;
+        for _ in 0..count {
+            if subrow + 1 < self.row_count(line_index) {
+                subrow += 1;
+            } else if line_index < last_line {
+                line_index += 1;
+                subrow = 0;
+            } else {
+                break;
+            }
+        }
+        (line_index, subrow)
+    }
+
+    /// Scrolls the view by `count` display rows, leaving the caret where it
+    /// is unless that goes off screen.
+    fn scroll_rows(&mut self, direction: Direction, count: usize) {
+        let top = self.top();
+        self.set_top(match direction {
+            Direction::Up => self.rows_up(top, count),
+            _ => self.rows_down(top, count),
+        });
+        self.keep_caret_on_screen();
     }
 
+    /// Moves the caret to the nearest row on screen, and `scrolloff` rows
+    /// away from its top and bottom unless the buffer starts or ends there,
+    /// keeping its column.
+    fn keep_caret_on_screen(&mut self) {
+        let top = self.top();
+        let margin = self.scrolloff();
+        let bottom = self.rows_down(top, self.size.height.saturating_sub(1));
+        let highest = if top == (0, 0) { top } else { self.rows_down(top, margin) };
+        let lowest = if self.rows_down(bottom, 1) == bottom { bottom } else { self.rows_up(bottom, margin) };
+        let (caret, col) = self.caret_row();
+        if caret < highest {
+            self.move_caret_to_row(highest, col);
+        } else if caret > lowest {
+            self.move_caret_to_row(lowest, col);
+        }
+    }
+
+    /// Moves the caret `count` display rows and scrolls the view as many,
+    /// or half a screen without a count.
+    fn scroll_half_page(&mut self, direction: Direction, count: Option<usize>) {
+        let rows = count.unwrap_or(self.size.height / 2).max(1);
+        let (caret, col) = self.caret_row();
+        let target = match direction {
+            Direction::Up => self.rows_up(caret, rows),
+            _ => self.rows_down(caret, rows),
+        };
+        self.move_caret_to_row(target, col);
+        self.scroll_rows(direction, rows);
+        self.scroll_text_location_into_view();
+    }
+
+    /// Scrolls the view so that the caret's row has `rows_above` rows above
+    /// it, or as many as there are.
+    fn scroll_caret_to(&mut self, rows_above: usize) {
+        let (caret, _) = self.caret_row();
+        self.set_top(self.rows_up(caret, rows_above));
+    }
+
+    /// The location drawn at the cell `at` of the screen, or the nearest
+    /// one: the end of the line right of it, the last line below the buffer
+    /// and the start of the line in the gutter.
+    fn location_at(&self, at: Coords) -> Location {
+        let row = at.row.saturating_sub(self.origin.row).min(self.size.height.saturating_sub(1));
+        let mut col = at.col.saturating_sub(self.origin.col).saturating_sub(self.gutter().width());
+        if !self.options.wrap {
+            col += self.scroll_offset.col;
+        }
+        let (line_index, subrow) = self.rows_down((self.scroll_offset.row, self.top_subrow), row);
+        Location { line_index, grapheme_index: self.grapheme_on_row(line_index, subrow, col) }
+    }
+
+    /// How many screen rows the line at `line_index` takes up.
+    fn row_count(&self, line_index: usize) -> usize {
+        self.buffer.borrow().line(line_index).map_or(1, |line| self.wrap_starts(line).len())
+    }
+
+    /// Where each screen row of `line` starts, a single row unless wrapping.
+    fn wrap_starts(&self, line: &Line) -> Vec<usize> {
+        if !self.options.wrap {
+            return vec![0];
+        }
+        let width = self.text_width();
+        let continuation_width = width.saturating_sub(self.showbreak_width());
+        line.wrap(width, continuation_width, self.options.linebreak, self.options.tabstop)
+    }
+
+    fn showbreak_width(&self) -> usize {
+        let showbreak = Line::from(&self.options.showbreak);
+        showbreak.width_until(showbreak.grapheme_count(), self.options.tabstop)
+    }
+
+    /// Where `location` is drawn relative to the start of its line: the row
+    /// within the wrapped line, and the column, counting a `showbreak` but
+    /// not the gutter or horizontal scrolling.
+    fn display_position(&self, location: Location) -> Coords {
+        let buffer = self.buffer.borrow();
+        let Some(line) = buffer.line(location.line_index) else {
+            return Coords::default();
+        };
+        let column = line.width_until(location.grapheme_index, self.options.tabstop);
+        let starts = self.wrap_starts(line);
+        let row = starts.iter().rposition(|start| *start <= location.grapheme_index).unwrap_or_default();
+        let indent = if row > 0 { self.showbreak_width() } else { 0 };
+        let col = column - line.width_until(starts[row], self.options.tabstop) + indent;
+        if self.options.wrap && col >= self.text_width() && row + 1 == starts.len() {
+            return Coords { row: row + 1, col: self.showbreak_width() };
+        }
+        Coords { row, col }
+    }
+
+    /// The display rows between the top of the view and `location`.
+    fn rows_from_top(&self, location: Location) -> usize {
+        let mut rows = 0;
+        let mut subrow = self.top_subrow;
+        for line_index in self.scroll_offset.row..location.line_index {
+            rows += self.row_count(line_index).saturating_sub(subrow);
+            subrow = 0;
+        }
+        (rows + self.display_position(location).row).saturating_sub(subrow)
+    }
+
+    /// Where the caret goes on the screen.
     pub fn caret_position(&self) -> Coords {
-        self.text_location_to_position()
-            .saturating_sub(self.scroll_offset)
+        let Coords { row, col } = if let Some(prompt) = &self.prompt {
+            let message = Line::from(&prompt.message);
+            Coords {
+                row: self.size.height.saturating_sub(1),
+                col: message.width_until(message.grapheme_count(), self.options.tabstop).min(self.size.width.saturating_sub(1)),
+            }
+        } else if self.options.wrap {
+            let Coords { col, .. } = self.display_position(self.text_location);
+            Coords { row: self.rows_from_top(self.text_location), col: col + self.gutter().width() }
+        } else {
+            let Coords { row, col } = self.text_location_to_position()
+                .saturating_sub(self.scroll_offset);
+            Coords { row, col: col + self.gutter().width() }
+        };
+        Coords {
+            row: self.origin.row.saturating_add(row),
+            col: self.origin.col.saturating_add(col),
+        }
     }
 
     /* unused (i think)
@@ -492,73 +1665,77 @@ impl View {
 
     pub fn text_location_to_position(&self) -> Coords {
         let row = self.text_location.line_index;
-        let col = self.buffer.lines.get(row).map_or(0, |line| {
-            line.width_until(self.text_location.grapheme_index)
+        let col = self.buffer.borrow().line(row).map_or(0, |line| {
+            line.width_until(self.text_location.grapheme_index, self.options.tabstop)
         });
         Coords {row, col}
     }
 
-     fn move_text_location(&mut self, direction: Direction) {
-         let Size { height, .. } = self.size;
+    fn move_text_location(&mut self, direction: Direction) {
         match direction {
-            Direction::Up => self.move_up(1),
-            Direction::Down => self.move_down(1),
-            Direction::Left => self.move_left(),
-            Direction::Right => self.move_right(),
+            Direction::Up => self.apply_motion(Motion::Up(1)),
+            Direction::Down => self.apply_motion(Motion::Down(1)),
+            Direction::Left => self.apply_motion(Motion::Left),
+            Direction::Right => self.apply_motion(Motion::Right),
         }
         self.scroll_text_location_into_view();
     }
 
-    fn move_up(&mut self, step: usize) {
-        self.text_location.line_index = self.text_location.line_index.saturating_sub(step);
-        self.snap_to_valid_grapheme();
-    }
-    fn move_down(&mut self, step: usize) {
-        self.text_location.line_index = self.text_location.line_index.saturating_add(step);
-        self.snap_to_valid_grapheme();
-        self.snap_to_valid_line();
-    }
-    #[allow(clippy::arithmetic_side_effects)]
-    fn move_right(&mut self) {
-        let line_width = self
-            .buffer
-            .lines
-            .get(self.text_location.line_index)
-            .map_or(0, Line::grapheme_count);
-        if self.text_location.grapheme_index < line_width {
-            self.text_location.grapheme_index += 1;
-        }
+    /// Moves the caret to the screen row above or below, which is the
+    /// previous or next line unless it is wrapped, keeping the column.
+    fn move_display_row(&mut self, direction: Direction) {
+        let Coords { row, col } = self.display_position(self.text_location);
+        let line_index = self.text_location.line_index;
+        let target = match direction {
+            Direction::Down if row + 1 < self.row_count(line_index) => (line_index, row + 1),
+            Direction::Down if line_index + 1 < self.buffer.borrow().height() => (line_index + 1, 0),
+            Direction::Up if row > 0 => (line_index, row - 1),
+            Direction::Up if line_index > 0 => (line_index - 1, self.row_count(line_index - 1).saturating_sub(1)),
+            _ => return,
+        };
+        self.text_location = Location { line_index: target.0, grapheme_index: self.grapheme_on_row(target.0, target.1, col) };
+        self.scroll_text_location_into_view();
     }
-    #[allow(clippy::arithmetic_side_effects)]
-    fn move_left(&mut self) {
-        if self.text_location.grapheme_index > 0 {
-            self.text_location.grapheme_index -= 1;
+
+    /// The grapheme drawn at column `col` of row `row` of a wrapped line,
+    /// or the closest one on that row.
+    fn grapheme_on_row(&self, line_index: usize, row: usize, col: usize) -> usize {
+        let buffer = self.buffer.borrow();
+        let Some(line) = buffer.line(line_index) else {
+            return 0;
+        };
+        let starts = self.wrap_starts(line);
+        let start = starts.get(row).copied().unwrap_or_default();
+        let indent = if row > 0 { self.showbreak_width() } else { 0 };
+        let index = line.grapheme_index_at(line.width_until(start, self.options.tabstop) + col.saturating_sub(indent), self.options.tabstop);
+        match starts.get(row + 1) {
+            Some(next) => index.clamp(start, next.saturating_sub(1)),
+            None => index.max(start),
         }
     }
-    
-    fn move_to_start_of_line(&mut self) {
-        self.text_location.grapheme_index = 0;
-    }
-    fn move_to_end_of_line(&mut self) {
-        self.text_location.grapheme_index = self
-            .buffer
-            .lines
-            .get(self.text_location.line_index)
-            .map_or(0, Line::grapheme_count);
+
+    fn apply_motion(&mut self, motion: Motion) {
+        // Large files only have the lines around the caret loaded, so the
+        // line moved to is loaded before the column on it is worked out.
+        let line_index = motion.apply(&self.buffer.borrow(), self.text_location).line_index;
+        self.buffer.borrow_mut().ensure_loaded(line_index..line_index.saturating_add(1));
+        self.text_location = motion.apply(&self.buffer.borrow(), self.text_location);
     }
 
     fn snap_to_valid_grapheme(&mut self) {
+        let line_index = self.text_location.line_index;
+        self.buffer.borrow_mut().ensure_loaded(line_index..line_index.saturating_add(1));
         self.text_location.grapheme_index = self
             .buffer
-            .lines
-            .get(self.text_location.line_index)
+            .borrow()
+            .line(line_index)
             .map_or(0, |line| {
                 min(line.grapheme_count(), self.text_location.grapheme_index)
             });
     }
 
     fn snap_to_valid_line(&mut self) {
-        self.text_location.line_index = min(self.text_location.line_index, self.buffer.height());
+        self.text_location.line_index = min(self.text_location.line_index, self.buffer.borrow().height());
     }
 
 }
@@ -567,17 +1744,32 @@ impl Default for View {
     fn default() -> Self {
         let terminal_size = Terminal::size().unwrap_or_default();
         Self {
-            buffer: Buffer::default(),
+            buffer: Rc::new(RefCell::new(Buffer::default())),
             needs_redraw: true,
+            rendered_revision: None,
+            rendered_gutter: None,
+            origin: Coords::default(),
             size: Size {
                 width: terminal_size.width,
-                height: terminal_size.height - 1,
+                // The status line and the message line take the bottom two.
+                height: terminal_size.height.saturating_sub(2),
             },
             selection: Selection::default(),
             text_location: Location::default(),
             scroll_offset: Coords::default(),
+            top_subrow: 0,
             bmode: Bmode::Normal,
-            clipboard: String::default(),
+            clipboard: Rc::default(),
+            messages: Vec::new(),
+            pending_keys: String::new(),
+            count: None,
+            recording: None,
+            command_line: String::default(),
+            last_search: String::default(),
+            pending_search: None,
+            prompt: None,
+            options: Options::default(),
+            last_click: None,
         }
     }
 }
@@ -589,6 +1781,8 @@ impl Bmode {
             Bmode::Normal => "Normal".to_string(),
             Bmode::Replace => "Replace".to_string(),
             Bmode::Visual => "Visual".to_string(),
+            Bmode::Command => "Command".to_string(),
+            Bmode::Search => "Search".to_string(),
         }
     }
 }
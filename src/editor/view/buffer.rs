@@ -1,93 +1,593 @@
-use std::io::{Write, Error};
-use std::fs::{read_to_string, File};
+use std::io::{Write, Error, ErrorKind};
+use std::fs::{metadata, read, read_to_string, File};
+use std::hash::{DefaultHasher, Hash, Hasher};
+use std::ops::Range;
+use std::time::SystemTime;
+use super::filetype::Filetype;
+use super::highlight::{Highlighter, Token};
+use super::largefile::{BackgroundSearch, LargeFile};
 use super::line::Line;
+use super::swap::Swap;
 use super::Location;
 
+/// Files bigger than this are opened in large-file mode: read-only and
+/// loaded lazily, a window of lines at a time.
+const LARGE_FILE_THRESHOLD: u64 = 64 * 1024 * 1024;
+const UNDO_LEVELS: usize = 100;
+
+/// What the file looked like on disk when it was last read or written, to
+/// notice other programs changing it behind our back.
+struct DiskState {
+    modified: Option<SystemTime>,
+    len: u64,
+    hash: u64,
+}
+
+impl DiskState {
+    fn read(filename: &str) -> Result<Self, Error> {
+        let bytes = read(filename)?;
+        Self::of(filename, &bytes)
+    }
+
+    /// The state of `filename`, whose content was just read or written as
+    /// `bytes`.
+    fn of(filename: &str, bytes: &[u8]) -> Result<Self, Error> {
+        let meta = metadata(filename)?;
+        let mut hasher = DefaultHasher::new();
+        bytes.hash(&mut hasher);
+        Ok(Self {
+            modified: meta.modified().ok(),
+            len: meta.len(),
+            hash: hasher.finish(),
+        })
+    }
+}
+
+/// How the file on disk differs from when it was last read or written.
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub(crate) enum DiskChange {
+    Unchanged,
+    Modified,
+    Deleted,
+}
+
+/// How lines end in the file, kept as it was found when the file is
+/// written back.
+#[derive(Copy, Clone, PartialEq, Eq, Default)]
+pub enum LineEnding {
+    #[default]
+    Unix,
+    Dos,
+}
+
+impl LineEnding {
+    #[must_use]
+    pub const fn name(self) -> &'static str {
+        match self {
+            Self::Unix => "unix",
+            Self::Dos => "dos",
+        }
+    }
+
+    const fn as_str(self) -> &'static str {
+        match self {
+            Self::Unix => "\n",
+            Self::Dos => "\r\n",
+        }
+    }
+}
+
+/// One change to the lines: `removed` were taken out at `start`, and
+/// `inserted` put in their place.
+struct Edit {
+    start: usize,
+    removed: Vec<Line>,
+    inserted: Vec<Line>,
+}
+
+/// The edits made by one command, undone and redone together.
+struct UndoStep {
+    edits: Vec<Edit>,
+    /// Where the caret was before the command.
+    location: Location,
+    /// The version of the content the edits started from.
+    version: u64,
+}
+
+/// The text of a file being edited, as lines of graphemes. Edits go through
+/// its methods, which keep the syntax highlighting, the undo history and
+/// the modified flag in step. They do nothing to a read-only buffer.
 pub struct Buffer {
-    pub lines: Vec<Line>,
-    pub filename: String,
-    pub dirty: bool,
+    lines: Vec<Line>,
+    filename: String,
+    read_only: bool,
+    line_ending: LineEnding,
+    large: Option<LargeFile>,
+    revision: u64,
+    /// Names the content as it is: every edit makes up a new version, while
+    /// undoing and redoing go back to the versions they started from.
+    version: u64,
+    /// The version last read from or written to the file, if any.
+    saved_version: Option<u64>,
+    swap: Option<Swap>,
+    swap_checked: bool,
+    disk: Option<DiskState>,
+    undo_stack: Vec<UndoStep>,
+    redo_stack: Vec<UndoStep>,
+    highlighter: Highlighter,
 }
 
 impl Buffer {
+    /// Loads `filename`, or starts an empty buffer for it if it does not exist yet.
+    ///
+    /// # Errors
+    ///
+    /// If the file exists but cannot be read.
+    pub fn open(filename: &str) -> Result<Self, Error> {
+        match Self::load(filename) {
+            Err(err) if err.kind() == ErrorKind::NotFound => Ok(Self::named(filename)),
+            result => result,
+        }
+    }
+
+    /// Loads `filename`, read-only and a window of lines at a time if it is
+    /// very large.
+    ///
+    /// # Errors
+    ///
+    /// If the file does not exist or cannot be read.
     pub fn load(filename: &str) -> Result<Self, Error>{
-        let file_contents = read_to_string(filename)?;
+        if metadata(filename)?.len() > LARGE_FILE_THRESHOLD {
+            return Ok(Self {
+                large: Some(LargeFile::open(filename)?),
+                read_only: true,
+                ..Self::named(filename)
+            });
+        }
+        let bytes = read(filename)?;
+        let disk = DiskState::of(filename, &bytes).ok();
+        let file_contents = String::from_utf8(bytes).map_err(|err| Error::new(ErrorKind::InvalidData, err))?;
         let mut lines = Vec::new();
         for line in file_contents.lines() {
             lines.push(Line::from(line));
         }
-        Ok(Self {lines, filename: filename.to_string(), dirty: false})
+        let line_ending = if file_contents.contains("\r\n") { LineEnding::Dos } else { LineEnding::Unix };
+        Ok(Self {lines, line_ending, disk, ..Self::named(filename)})
     }
 
+    #[must_use]
+    pub fn named(filename: &str) -> Self {
+        Self {
+            filename: filename.to_string(),
+            ..Self::default()
+        }
+    }
+
+    /// Writes the lines to the file, ending them as they were when it was
+    /// read.
+    ///
+    /// # Errors
+    ///
+    /// If the buffer is read-only or the file cannot be written.
     pub fn save(&mut self) -> Result<(), Error> {
-        if let filename = &self.filename {
-            let mut file = File::create(filename)?;
-            for line in &self.lines {
-                writeln!(file, "{line}")?;
-            }
-            self.dirty = false
+        if self.read_only {
+            return Err(Error::new(ErrorKind::PermissionDenied, "buffer is read-only"));
+        }
+        let mut contents = String::new();
+        for line in &self.lines {
+            contents.push_str(&line.to_string());
+            contents.push_str(self.line_ending.as_str());
+        }
+        File::create(&self.filename)?.write_all(contents.as_bytes())?;
+        self.saved_version = Some(self.version);
+        self.disk = DiskState::of(&self.filename, contents.as_bytes()).ok();
+        if let Some(swap) = &mut self.swap {
+            swap.remove();
         }
         Ok(())
     }
 
+    /// Whether the file was changed or deleted by someone else since we
+    /// last read or wrote it. Only the size and mtime are looked at unless
+    /// they differ, in which case the content hash decides.
+    pub(crate) fn disk_change(&mut self) -> DiskChange {
+        if self.large.is_some() {
+            return DiskChange::Unchanged;
+        }
+        let Ok(meta) = metadata(&self.filename) else {
+            return if self.disk.is_some() { DiskChange::Deleted } else { DiskChange::Unchanged };
+        };
+        let Some(disk) = &mut self.disk else {
+            return DiskChange::Modified;
+        };
+        if meta.modified().ok() == disk.modified && meta.len() == disk.len {
+            return DiskChange::Unchanged;
+        }
+        match DiskState::read(&self.filename) {
+            Ok(current) if current.hash == disk.hash => {
+                *disk = current;
+                DiskChange::Unchanged
+            },
+            Ok(_) => DiskChange::Modified,
+            Err(_) => DiskChange::Unchanged,
+        }
+    }
+
+    /// Accepts whatever is on disk now as the version we are editing against,
+    /// so the change is not reported again. If the file is gone, the buffer
+    /// no longer matches anything on disk and counts as modified.
+    pub(crate) fn acknowledge_disk_state(&mut self) {
+        self.disk = DiskState::read(&self.filename).ok();
+        if self.disk.is_none() {
+            self.saved_version = None;
+        }
+    }
+
+    pub(crate) fn disk_lines(&self) -> Vec<String> {
+        read_to_string(&self.filename).map_or_else(|_| Vec::new(), |text| text.lines().map(String::from).collect())
+    }
+
+    /// Counts up with every change to the content.
+    #[must_use]
+    pub const fn revision(&self) -> u64 {
+        self.revision
+    }
+
+    /// Reads the file again, throwing away the content and the swap file.
+    /// Returns whether that worked; the buffer is left alone if not.
+    pub(crate) fn reload(&mut self) -> bool {
+        let Ok(reloaded) = Self::load(&self.filename) else {
+            return false;
+        };
+        self.discard_swap();
+        self.replace_with(reloaded);
+        self.claim_swap();
+        true
+    }
+
+    /// Takes over the content of a freshly loaded copy of the same file. The
+    /// revision keeps counting up, so views showing the buffer redraw.
+    pub(crate) fn replace_with(&mut self, reloaded: Self) {
+        let revision = self.revision.wrapping_add(1);
+        *self = reloaded;
+        self.revision = revision;
+    }
+
+    #[must_use]
+    pub fn filename(&self) -> &str {
+        &self.filename
+    }
+
+    /// Whether the buffer refuses edits and writes, as large files and
+    /// files opened read-only after finding a swap file do.
+    #[must_use]
+    pub const fn is_read_only(&self) -> bool {
+        self.read_only
+    }
+
+    pub(crate) const fn set_read_only(&mut self) {
+        self.read_only = true;
+    }
+
+    /// How lines end when the buffer is written.
+    #[must_use]
+    pub const fn line_ending(&self) -> LineEnding {
+        self.line_ending
+    }
+
+    /// Whether the content differs from what was last read or written.
+    #[must_use]
+    pub fn is_dirty(&self) -> bool {
+        self.saved_version != Some(self.version)
+    }
+
+    /// Replaces the lines in `range` with `lines`, keeping the highlighting
+    /// in step, and returns the lines taken out.
+    fn splice_lines(&mut self, range: Range<usize>, lines: Vec<Line>) -> Vec<Line> {
+        let start = range.start;
+        let count = lines.len();
+        let removed = self.lines.splice(range.clone(), lines).collect();
+        self.highlighter.removed(range);
+        self.highlighter.inserted(start, count);
+        self.revision = self.revision.wrapping_add(1);
+        removed
+    }
+
+    /// Replaces the lines in `range` with `lines` as part of the current
+    /// undo step.
+    fn edit(&mut self, range: Range<usize>, lines: Vec<Line>) {
+        let start = range.start;
+        let inserted = lines.clone();
+        let removed = self.splice_lines(range, lines);
+        if self.undo_stack.is_empty() {
+            self.undo_stack.push(UndoStep { edits: Vec::new(), location: Location::default(), version: self.version });
+        }
+        if let Some(step) = self.undo_stack.last_mut() {
+            step.edits.push(Edit { start, removed, inserted });
+        }
+        self.version = self.revision;
+        self.redo_stack.clear();
+    }
+
+    /// Starts a new undo step, so that the changes made from here on are
+    /// undone together, and `undo` puts the caret back at `location`. The
+    /// editor calls it before every command that may change the buffer; a
+    /// step left without changes is reused by the next one. Edits made
+    /// before the first checkpoint make up one step.
+    pub fn checkpoint(&mut self, location: Location) {
+        if self.read_only {
+            return;
+        }
+        match self.undo_stack.last_mut() {
+            Some(step) if step.edits.is_empty() => {
+                step.location = location;
+                step.version = self.version;
+            },
+            _ => {
+                if self.undo_stack.len() == UNDO_LEVELS {
+                    self.undo_stack.remove(0);
+                }
+                self.undo_stack.push(UndoStep { edits: Vec::new(), location, version: self.version });
+            },
+        }
+    }
+
+    /// Reverts the last change, returning where the caret was before it.
+    /// The caret is at `location` now, which is where `redo` puts it back.
+    pub fn undo(&mut self, location: Location) -> Option<Location> {
+        while self.undo_stack.last().is_some_and(|step| step.edits.is_empty()) {
+            self.undo_stack.pop();
+        }
+        let step = self.undo_stack.pop()?;
+        let before = step.location;
+        let reverted = self.revert(step, location);
+        self.redo_stack.push(reverted);
+        Some(before)
+    }
+
+    /// Makes the last change undone again, returning where the caret was
+    /// when it was undone.
+    pub fn redo(&mut self, location: Location) -> Option<Location> {
+        let step = self.redo_stack.pop()?;
+        let before = step.location;
+        let reverted = self.revert(step, location);
+        self.undo_stack.push(reverted);
+        Some(before)
+    }
+
+    /// Undoes the edits of `step`, last one first, going back to the version
+    /// it started from. Returns the step that does them again, starting from
+    /// `location`.
+    fn revert(&mut self, step: UndoStep, location: Location) -> UndoStep {
+        let mut edits = Vec::with_capacity(step.edits.len());
+        for edit in step.edits.into_iter().rev() {
+            let range = edit.start..edit.start + edit.inserted.len();
+            let inserted = edit.removed.clone();
+            self.splice_lines(range, edit.removed);
+            edits.push(Edit { start: edit.start, removed: edit.inserted, inserted });
+        }
+        let version = std::mem::replace(&mut self.version, step.version);
+        UndoStep { edits, location, version }
+    }
+
+    /// Replaces the whole content, e.g. with what was recovered from a swap
+    /// file. The buffer counts as modified afterwards.
+    pub fn replace_lines(&mut self, lines: &[String]) {
+        if self.read_only {
+            return;
+        }
+        let lines = lines.iter().map(|line| Line::from(line)).collect();
+        self.edit(0..self.lines.len(), lines);
+    }
+
+    pub fn text_lines(&self) -> Vec<String> {
+        self.lines.iter().map(ToString::to_string).collect()
+    }
+
+    /// Starts journaling this buffer to its swap file. Only done once any
+    /// swap file already present has been dealt with.
+    pub(crate) fn claim_swap(&mut self) {
+        self.swap_checked = true;
+        if !self.read_only && self.swap.is_none() {
+            self.swap = Some(Swap::new(&self.filename));
+        }
+    }
+
+    /// Whether a swap file left behind for this buffer still has to be
+    /// looked for. That happens the first time the buffer is shown.
+    pub(crate) const fn needs_swap_check(&self) -> bool {
+        !self.swap_checked && !self.read_only
+    }
+
+    /// Keeps the swap file's contents up to date in memory, and writes them
+    /// out every so often.
+    pub(crate) fn write_swap(&mut self) {
+        if !self.is_dirty() {
+            return;
+        }
+        if let Some(swap) = &mut self.swap {
+            swap.keep(self.revision, &self.lines);
+            if swap.is_due(self.revision) {
+                let _ = swap.write(self.revision, &self.lines);
+            }
+        }
+    }
+
+    /// Brings the swap file up to date right away, for when vih panics.
+    pub(crate) fn flush_swap(&self) {
+        if let Some(swap) = &self.swap
+            && self.is_dirty() {
+            let _ = swap.flush(self.revision, &self.lines);
+        }
+    }
+
+    pub(crate) fn discard_swap(&mut self) {
+        if let Some(swap) = &mut self.swap {
+            swap.remove();
+        }
+    }
+
+    #[must_use]
     pub fn is_empty(&self) -> bool {
-        self.lines.is_empty()
+        self.large.is_none() && self.lines.is_empty()
+    }
+
+    pub fn is_indexing(&self) -> bool {
+        self.large.as_ref().is_some_and(LargeFile::is_indexing)
     }
 
     pub fn height(&self) -> usize {
-        self.lines.len()
+        self.large.as_ref().map_or(self.lines.len(), LargeFile::line_count)
+    }
+
+    pub(crate) fn filetype(&self) -> Filetype {
+        Filetype::from_filename(&self.filename)
+    }
+
+    /// Tokenizes the lines before `end` that are not yet. Large files are
+    /// not highlighted.
+    pub(crate) fn update_highlights(&mut self, end: usize) {
+        if self.large.is_some() {
+            return;
+        }
+        let filetype = self.filetype();
+        let lines = &self.lines;
+        self.highlighter.update(filetype, |line_index| lines.get(line_index), end);
+    }
+
+    pub(crate) fn tokens(&self, line_index: usize) -> &[Token] {
+        self.highlighter.tokens(line_index)
+    }
+
+    /// The line at `line_index`. In a large file, `None` unless
+    /// `ensure_loaded` was called for it first.
+    #[must_use]
+    pub fn line(&self, line_index: usize) -> Option<&Line> {
+        match &self.large {
+            Some(large) => large.line(line_index),
+            None => self.lines.get(line_index),
+        }
+    }
+
+    /// Large files only hold a window of lines in memory; this has to be
+    /// called before looking at lines in `range` through `line`.
+    pub fn ensure_loaded(&mut self, range: Range<usize>) {
+        if let Some(large) = &mut self.large {
+            large.ensure_loaded(range);
+        }
+    }
+
+    /// Finds the next occurrence of `pattern` after `from`, wrapping around
+    /// the end of the buffer.
+    #[must_use]
+    pub fn search(&self, pattern: &str, from: Location) -> Option<Location> {
+        if let Some(large) = &self.large {
+            return large.search(pattern, from).wait();
+        }
+        let height = self.lines.len();
+        (0..=height).find_map(|step| {
+            let line_index = (from.line_index + step) % height.max(1);
+            let after = (step == 0).then_some(from.grapheme_index);
+            let grapheme_index = self.lines.get(line_index)?.find(pattern, after)?;
+            Some(Location { grapheme_index, line_index })
+        })
+    }
+
+    /// Like `search`, but for a large file, which takes a while to go
+    /// through, the search is left running in the background. `None` for
+    /// buffers held in memory, which are searched with `search`.
+    pub(crate) fn search_in_background(&self, pattern: &str, from: Location) -> Option<BackgroundSearch> {
+        self.large.as_ref().map(|large| large.search(pattern, from))
     }
 
     pub fn insert_char(&mut self, character: char, at: Location) {
-        if at.line_index > self.lines.len() {
+        if self.read_only {
             return;
         }
         if at.line_index == self.lines.len() {
-            self.lines.push(Line::from(&character.to_string()));
-            self.dirty = true;
-        } else if let Some(line) = self.lines.get_mut(at.line_index) {
+            self.edit(at.line_index..at.line_index, vec![Line::from(&character.to_string())]);
+        } else if let Some(line) = self.lines.get(at.line_index) {
+            let mut line = line.clone();
             line.insert_char(character, at.grapheme_index);
-            self.dirty = true;
+            self.edit(at.line_index..at.line_index + 1, vec![line]);
         }
     }
 
-    pub fn insert_text(&mut self, text: String, at: Location) {
-         let mut col = at.grapheme_index;
-         for i in text.chars() {
-             self.insert_char(i,Location {line_index: at.line_index, grapheme_index: col});
-             col +=1;
-         }
-     }
+    /// Inserts `text` at `at`, breaking the line wherever the text has a
+    /// line break. Returns where the inserted text ends.
+    pub fn insert_text(&mut self, text: &str, at: Location) -> Location {
+        if self.read_only || at.line_index > self.lines.len() || text.is_empty() {
+            return at;
+        }
+        let (range, mut first) = match self.lines.get(at.line_index) {
+            Some(line) => (at.line_index..at.line_index + 1, line.clone()),
+            None => (at.line_index..at.line_index, Line::default()),
+        };
+        let text = text.replace("\r\n", "\n").replace('\r', "\n");
+        let mut pieces = text.split('\n');
+        let tail = first.split(at.grapheme_index.min(first.grapheme_count()));
+        first.append(&Line::from(pieces.next().unwrap_or_default()));
+        let mut lines = vec![first];
+        lines.extend(pieces.map(Line::from));
+        let line_index = at.line_index + lines.len() - 1;
+        let mut end = at;
+        if let Some(last) = lines.last_mut() {
+            end = Location { grapheme_index: last.grapheme_count(), line_index };
+            last.append(&tail);
+        }
+        self.edit(range, lines);
+        end
+    }
 
     pub fn delete(&mut self, at: Location) {
-        if let Some(line) = self.lines.get(at.line_index) {
-            if at.grapheme_index >= line.grapheme_count() && self.lines.len() > at.line_index + 1 {
-                let next_line = self.lines.remove(at.line_index + 1);
-                self.lines[at.line_index].append(&next_line);
-                self.dirty = true;
-            } else if at.grapheme_index < line.grapheme_count() {
-                self.lines[at.line_index].delete(at.grapheme_index);
-                self.dirty = true;
-                // normal case
-            }
+        let Some(line) = self.lines.get(at.line_index).filter(|_| !self.read_only) else {
+            return;
+        };
+        if at.grapheme_index >= line.grapheme_count() && self.lines.len() > at.line_index + 1 {
+            let mut joined = line.clone();
+            joined.append(&self.lines[at.line_index + 1]);
+            self.edit(at.line_index..at.line_index + 2, vec![joined]);
+        } else if at.grapheme_index < line.grapheme_count() {
+            let mut line = line.clone();
+            line.delete(at.grapheme_index);
+            self.edit(at.line_index..at.line_index + 1, vec![line]);
         }
     }
 
     pub fn delete_line(&mut self, line_index: usize, start: usize, end: usize) {
-        for i in start..end { 
-            self.delete(Location {line_index: line_index, grapheme_index: start}); 
+        for _ in start..end {
+            self.delete(Location {line_index, grapheme_index: start});
         }
     }
 
     pub fn insert_line(&mut self, at: Location) {
+        if self.read_only {
+            return;
+        }
         if at.line_index == self.lines.len() {
-            self.lines.push(Line::default());
-            self.dirty = true;
-        } else if let Some(line) = self.lines.get_mut(at.line_index) {
+            self.edit(at.line_index..at.line_index, vec![Line::default()]);
+        } else if let Some(line) = self.lines.get(at.line_index) {
+            let mut line = line.clone();
             let newline = line.split(at.grapheme_index);
-            self.lines.insert(at.line_index + 1, newline);
-            self.dirty = true;
+            self.edit(at.line_index..at.line_index + 1, vec![line, newline]);
+        }
+    }
+
+    /// Deletes the text from `start` up to `end`, joining their lines.
+    pub fn delete_range(&mut self, start: Location, end: Location) {
+        if self.read_only {
+            return;
+        }
+        if start.line_index == end.line_index {
+            self.delete_line(start.line_index, start.grapheme_index, end.grapheme_index);
+            return;
         }
+        let (Some(first), Some(last)) = (self.lines.get(start.line_index), self.lines.get(end.line_index)) else {
+            return;
+        };
+        let mut joined = Line::from(&first.text(0..start.grapheme_index));
+        joined.append(&Line::from(&last.text(end.grapheme_index..last.grapheme_count())));
+        self.edit(start.line_index..end.line_index + 1, vec![joined]);
     }
 }
 
@@ -96,7 +596,18 @@ impl Default for Buffer {
         Self {
             lines: Vec::<Line>::default(),
             filename: "default.txt".to_string(),
-            dirty: false,
+            read_only: false,
+            line_ending: LineEnding::default(),
+            large: None,
+            revision: 0,
+            version: 0,
+            saved_version: Some(0),
+            swap: None,
+            swap_checked: false,
+            disk: None,
+            undo_stack: Vec::new(),
+            redo_stack: Vec::new(),
+            highlighter: Highlighter::default(),
         }
     }
 }
@@ -0,0 +1,151 @@
+/// Beyond this many removed and added lines the search for the shortest edit
+/// is given up, and the differing middle part is reported as entirely removed
+/// and re-added. What the search keeps grows with the square of the edits.
+const MAX_EDITS: usize = 2_000;
+const CONTEXT: usize = 3;
+
+#[derive(Copy, Clone, PartialEq)]
+enum Change {
+    Equal,
+    Delete,
+    Insert,
+}
+
+/// Line-based diff of `old` against `new`, formatted as unified diff hunks.
+pub fn unified(old: &[String], new: &[String]) -> Vec<String> {
+    let changes = diff(old, new);
+    let mut output = Vec::new();
+    let (mut old_index, mut new_index) = (0, 0);
+    let mut i = 0;
+    while i < changes.len() {
+        if changes[i] == Change::Equal {
+            old_index += 1;
+            new_index += 1;
+            i += 1;
+            continue;
+        }
+        let start = i.saturating_sub(CONTEXT);
+        let mut end = i;
+        let mut equal_run = 0;
+        while end < changes.len() && equal_run <= 2 * CONTEXT {
+            equal_run = if changes[end] == Change::Equal { equal_run + 1 } else { 0 };
+            end += 1;
+        }
+        end -= equal_run.saturating_sub(CONTEXT);
+        let (old_start, new_start) = (old_index - (i - start), new_index - (i - start));
+        let hunk = &changes[start..end];
+        let old_count = hunk.iter().filter(|change| **change != Change::Insert).count();
+        let new_count = hunk.iter().filter(|change| **change != Change::Delete).count();
+        output.push(format!("@@ -{},{old_count} +{},{new_count} @@", old_start + 1, new_start + 1));
+        let (mut o, mut n) = (old_start, new_start);
+        for change in hunk {
+            match change {
+                Change::Equal => {
+                    output.push(format!(" {}", old[o]));
+                    o += 1;
+                    n += 1;
+                },
+                Change::Delete => {
+                    output.push(format!("-{}", old[o]));
+                    o += 1;
+                },
+                Change::Insert => {
+                    output.push(format!("+{}", new[n]));
+                    n += 1;
+                },
+            }
+        }
+        old_index = o;
+        new_index = n;
+        i = end;
+    }
+    output
+}
+
+fn diff(old: &[String], new: &[String]) -> Vec<Change> {
+    let prefix = old.iter().zip(new).take_while(|(a, b)| a == b).count();
+    let suffix = old[prefix..]
+        .iter()
+        .rev()
+        .zip(new[prefix..].iter().rev())
+        .take_while(|(a, b)| a == b)
+        .count();
+    let a = &old[prefix..old.len() - suffix];
+    let b = &new[prefix..new.len() - suffix];
+
+    let mut changes = vec![Change::Equal; prefix];
+    if let Some(middle) = shortest_edit(a, b) {
+        changes.extend(middle);
+    } else {
+        changes.extend(std::iter::repeat_n(Change::Delete, a.len()));
+        changes.extend(std::iter::repeat_n(Change::Insert, b.len()));
+    }
+    changes.extend(std::iter::repeat_n(Change::Equal, suffix));
+    changes
+}
+
+/// Myers' shortest edit script from `old` to `new`, or `None` if it takes more
+/// than `MAX_EDITS` edits.
+///
+/// Round `d` tracks how far along `old` the paths of `d` edits reach on each
+/// diagonal they can end on, `x - y` being one of `-d, -d + 2, ..., d`; entry
+/// `i` of the round is diagonal `2i - d`.
+fn shortest_edit(old: &[String], new: &[String]) -> Option<Vec<Change>> {
+    let (old_end, new_end) = (old.len(), new.len());
+    let snake = |mut x: usize, mut y: usize| {
+        while x < old_end && y < new_end && old[x] == new[y] {
+            x += 1;
+            y += 1;
+        }
+        x
+    };
+    // Whether entry `i` of round `d` is best reached by an insertion from
+    // entry `i` of the round before, rather than a deletion from `i - 1`.
+    let came_down = |previous: &[usize], d: usize, i: usize| i == 0 || (i != d && previous[i - 1] < previous[i]);
+    // Where the diagonal of the end point sits in round `d`, if it is one
+    // of the round's.
+    let end = |d: usize| {
+        (old_end + d)
+            .checked_sub(new_end)
+            .filter(|twice| twice % 2 == 0 && *twice <= 2 * d)
+            .map(|twice| twice / 2)
+    };
+    let mut rounds = vec![vec![snake(0, 0)]];
+    let mut d = 0;
+    let mut i = loop {
+        if let Some(i) = end(d)
+            && rounds[d][i] >= old_end {
+            break i;
+        }
+        if d == MAX_EDITS {
+            return None;
+        }
+        d += 1;
+        let previous = &rounds[d - 1];
+        let round = (0..=d)
+            .map(|i| {
+                let x = if came_down(previous, d, i) { previous[i] } else { previous[i - 1] + 1 };
+                snake(x, x + d - 2 * i)
+            })
+            .collect();
+        rounds.push(round);
+    };
+
+    let mut changes = Vec::with_capacity(old_end + new_end);
+    let mut x = old_end;
+    for d in (1..=d).rev() {
+        let previous = &rounds[d - 1];
+        let (start, change) = if came_down(previous, d, i) {
+            (previous[i], Change::Insert)
+        } else {
+            i -= 1;
+            (previous[i] + 1, Change::Delete)
+        };
+        changes.extend(std::iter::repeat_n(Change::Equal, x - start));
+        changes.push(change);
+        x = previous[i];
+    }
+    changes.extend(std::iter::repeat_n(Change::Equal, x));
+    changes.reverse();
+    Some(changes)
+}
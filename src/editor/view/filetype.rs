@@ -0,0 +1,57 @@
+use std::path::Path;
+use super::indent::{self, IndentRules};
+
+/// The language of a buffer, told from its file name. Decides how lines
+/// are indented.
+#[derive(Copy, Clone, PartialEq, Eq, Default)]
+pub enum Filetype {
+    Rust,
+    Python,
+    C,
+    Json,
+    Toml,
+    Markdown,
+    Shell,
+    #[default]
+    Text,
+}
+
+impl Filetype {
+    pub fn from_filename(filename: &str) -> Self {
+        let path = Path::new(filename);
+        let name = path.file_name().and_then(|name| name.to_str()).unwrap_or_default();
+        let extension = path.extension().and_then(|extension| extension.to_str()).unwrap_or_default();
+        match (name, extension.to_ascii_lowercase().as_str()) {
+            (_, "rs") => Self::Rust,
+            (_, "py" | "pyw" | "pyi") => Self::Python,
+            (_, "c" | "h" | "cc" | "cpp" | "cxx" | "hpp" | "hh") => Self::C,
+            (_, "json") => Self::Json,
+            ("Cargo.lock", _) | (_, "toml") => Self::Toml,
+            (_, "md" | "markdown") => Self::Markdown,
+            (".bashrc" | ".bash_profile" | ".profile" | ".zshrc", _) | (_, "sh" | "bash" | "zsh") => Self::Shell,
+            _ => Self::Text,
+        }
+    }
+
+    pub const fn name(self) -> &'static str {
+        match self {
+            Self::Rust => "rust",
+            Self::Python => "python",
+            Self::C => "c",
+            Self::Json => "json",
+            Self::Toml => "toml",
+            Self::Markdown => "markdown",
+            Self::Shell => "sh",
+            Self::Text => "text",
+        }
+    }
+
+    pub const fn indent_rules(self) -> &'static IndentRules {
+        match self {
+            Self::Rust | Self::C | Self::Json | Self::Toml => &indent::BRACKETS,
+            Self::Python => &indent::PYTHON,
+            Self::Shell => &indent::SHELL,
+            Self::Markdown | Self::Text => &indent::PLAIN,
+        }
+    }
+}
@@ -0,0 +1,77 @@
+use super::options::Options;
+
+/// Line numbers are given at least this many digits, so the gutter does not
+/// change width for every new order of magnitude in small files.
+const MIN_NUMBER_DIGITS: usize = 3;
+
+#[derive(Copy, Clone, PartialEq, Eq)]
+enum NumberMode {
+    Absolute,
+    /// Distance from the caret's line on every line.
+    Relative,
+    /// Distance from the caret's line, except on that line itself, which
+    /// shows its own number.
+    Hybrid,
+}
+
+/// One column of the gutter, followed by a space separating it from the next
+/// column or from the text. New kinds of columns, such as signs for
+/// diagnostics, are added here.
+#[derive(Copy, Clone, PartialEq, Eq)]
+enum Column {
+    LineNumbers { mode: NumberMode, digits: usize, current_line: usize },
+}
+
+impl Column {
+    const fn width(&self) -> usize {
+        match self {
+            Self::LineNumbers { digits, .. } => *digits + 1,
+        }
+    }
+
+    fn text(&self, line_index: usize) -> String {
+        match *self {
+            Self::LineNumbers { mode, digits, current_line } => {
+                let distance = line_index.abs_diff(current_line);
+                match mode {
+                    NumberMode::Hybrid if distance == 0 => format!("{:<digits$} ", line_index + 1),
+                    NumberMode::Relative | NumberMode::Hybrid => format!("{distance:>digits$} "),
+                    NumberMode::Absolute => format!("{:>digits$} ", line_index + 1),
+                }
+            },
+        }
+    }
+}
+
+/// The columns drawn to the left of the text of each line.
+#[derive(Clone, PartialEq, Eq, Default)]
+pub struct Gutter {
+    columns: Vec<Column>,
+}
+
+impl Gutter {
+    pub fn new(options: &Options, line_count: usize, current_line: usize) -> Self {
+        let mut columns = Vec::new();
+        let mode = match (options.number, options.relativenumber) {
+            (true, false) => Some(NumberMode::Absolute),
+            (false, true) => Some(NumberMode::Relative),
+            (true, true) => Some(NumberMode::Hybrid),
+            (false, false) => None,
+        };
+        if let Some(mode) = mode {
+            let digits = line_count.to_string().len().max(MIN_NUMBER_DIGITS);
+            let current_line = if mode == NumberMode::Absolute { 0 } else { current_line };
+            columns.push(Column::LineNumbers { mode, digits, current_line });
+        }
+        Self { columns }
+    }
+
+    pub fn width(&self) -> usize {
+        self.columns.iter().map(Column::width).sum()
+    }
+
+    /// The gutter for the line at `line_index`, `width()` columns wide.
+    pub fn text(&self, line_index: usize) -> String {
+        self.columns.iter().map(|column| column.text(line_index)).collect()
+    }
+}
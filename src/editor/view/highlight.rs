@@ -0,0 +1,186 @@
+use std::ops::Range;
+use unicode_segmentation::UnicodeSegmentation;
+use crate::editor::theme::Group;
+use super::filetype::Filetype;
+use super::line::Line;
+
+mod grammar;
+mod markdown;
+use grammar::Grammar;
+
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum TokenKind {
+    Comment,
+    String,
+    Number,
+    Keyword,
+    Type,
+    Constant,
+    /// A key of a table or object, as in TOML and JSON.
+    Key,
+    /// A shell variable such as `$HOME`.
+    Variable,
+    /// A preprocessor line, attribute or decorator.
+    Directive,
+    Heading,
+    Emphasis,
+    Code,
+    Link,
+}
+
+impl TokenKind {
+    pub const fn group(self) -> Group {
+        match self {
+            Self::Comment => Group::Comment,
+            Self::String => Group::String,
+            Self::Number => Group::Number,
+            Self::Keyword => Group::Keyword,
+            Self::Type => Group::Type,
+            Self::Constant => Group::Constant,
+            Self::Key => Group::Key,
+            Self::Variable => Group::Variable,
+            Self::Directive => Group::PreProc,
+            Self::Heading => Group::Title,
+            Self::Emphasis => Group::Emphasis,
+            Self::Code => Group::Code,
+            Self::Link => Group::Link,
+        }
+    }
+}
+
+/// A highlighted run of graphemes of a line.
+#[derive(Clone, Debug)]
+pub struct Token {
+    pub range: Range<usize>,
+    pub kind: TokenKind,
+}
+
+/// What the tokenizer is in the middle of at the start of a line, for
+/// constructs that carry on over several lines.
+#[derive(Copy, Clone, PartialEq, Eq, Default, Debug)]
+enum State {
+    #[default]
+    Normal,
+    BlockComment,
+    /// Inside a string opened by the grammar's quote with this index.
+    String(usize),
+    /// Inside a fenced Markdown code block.
+    CodeBlock,
+}
+
+#[derive(Copy, Clone)]
+enum Syntax {
+    Code(&'static Grammar),
+    Markdown,
+}
+
+impl Syntax {
+    const fn for_filetype(filetype: Filetype) -> Option<Self> {
+        match filetype {
+            Filetype::Rust => Some(Self::Code(&grammar::RUST)),
+            Filetype::Python => Some(Self::Code(&grammar::PYTHON)),
+            Filetype::C => Some(Self::Code(&grammar::C)),
+            Filetype::Json => Some(Self::Code(&grammar::JSON)),
+            Filetype::Toml => Some(Self::Code(&grammar::TOML)),
+            Filetype::Shell => Some(Self::Code(&grammar::SHELL)),
+            Filetype::Markdown => Some(Self::Markdown),
+            Filetype::Text => None,
+        }
+    }
+
+    fn highlight(self, line: &Line, start: State) -> Highlights {
+        let text = line.to_string();
+        let mut spans = Vec::new();
+        let end = match self {
+            Self::Code(grammar) => grammar.tokenize(&text, start, &mut spans),
+            Self::Markdown => markdown::tokenize(&text, start, &mut spans),
+        };
+        let boundaries: Vec<usize> = text.grapheme_indices(true).map(|(index, _)| index).collect();
+        let grapheme_index = |byte: usize| boundaries.partition_point(|boundary| *boundary < byte);
+        let tokens = spans
+            .into_iter()
+            .filter(|(range, _)| range.start < range.end)
+            .map(|(range, kind)| Token { range: grapheme_index(range.start)..grapheme_index(range.end), kind })
+            .collect();
+        Highlights { start, end, tokens }
+    }
+}
+
+struct Highlights {
+    start: State,
+    end: State,
+    tokens: Vec<Token>,
+}
+
+/// The tokens of every line of a buffer, worked out as far down as has been
+/// shown. An edit only drops the lines it touched; the lines after are
+/// tokenized again only if the edit changed the state they start in, as
+/// when opening a block comment.
+#[derive(Default)]
+pub struct Highlighter {
+    filetype: Option<Filetype>,
+    lines: Vec<Option<Highlights>>,
+    /// The lines before this one are up to date.
+    valid_until: usize,
+}
+
+impl Highlighter {
+    pub fn inserted(&mut self, line_index: usize, count: usize) {
+        if line_index <= self.lines.len() {
+            self.lines.splice(line_index..line_index, std::iter::repeat_with(|| None).take(count));
+        }
+        self.valid_until = self.valid_until.min(line_index);
+    }
+
+    pub fn removed(&mut self, lines: Range<usize>) {
+        let start = lines.start.min(self.lines.len());
+        let end = lines.end.min(self.lines.len());
+        self.lines.drain(start..end);
+        self.valid_until = self.valid_until.min(lines.start);
+    }
+
+    pub fn reset(&mut self) {
+        self.lines.clear();
+        self.valid_until = 0;
+    }
+
+    /// Brings the tokens of the lines before `end` up to date.
+    pub fn update<'a>(&mut self, filetype: Filetype, line: impl Fn(usize) -> Option<&'a Line>, end: usize) {
+        if self.filetype != Some(filetype) {
+            self.filetype = Some(filetype);
+            self.reset();
+        }
+        let Some(syntax) = Syntax::for_filetype(filetype) else {
+            return;
+        };
+        let mut state = self
+            .valid_until
+            .checked_sub(1)
+            .and_then(|previous| self.lines.get(previous)?.as_ref())
+            .map_or(State::default(), |highlights| highlights.end);
+        for line_index in self.valid_until..end {
+            let Some(line) = line(line_index) else {
+                break;
+            };
+            if self.lines.len() <= line_index {
+                self.lines.resize_with(line_index + 1, || None);
+            }
+            let highlights = &mut self.lines[line_index];
+            if highlights.as_ref().is_none_or(|highlights| highlights.start != state) {
+                *highlights = Some(syntax.highlight(line, state));
+            }
+            state = highlights.as_ref().map_or(State::default(), |highlights| highlights.end);
+            self.valid_until = line_index + 1;
+        }
+    }
+
+    pub fn tokens(&self, line_index: usize) -> &[Token] {
+        if line_index >= self.valid_until {
+            return &[];
+        }
+        self.lines
+            .get(line_index)
+            .and_then(Option::as_ref)
+            .map_or(&[], |highlights| highlights.tokens.as_slice())
+    }
+}
@@ -0,0 +1,364 @@
+use std::ops::Range;
+use super::{State, TokenKind};
+
+/// A kind of string literal: what opens and closes it, whether a backslash
+/// escapes the character after it, and whether it may run on over several
+/// lines.
+pub struct Quote {
+    open: &'static str,
+    close: &'static str,
+    escape: bool,
+    multiline: bool,
+    /// Only a single, possibly escaped, character between the quotes, so
+    /// that Rust lifetimes are not taken for one.
+    character: bool,
+}
+
+impl Quote {
+    const fn new(open: &'static str, close: &'static str) -> Self {
+        Self { open, close, escape: true, multiline: false, character: false }
+    }
+
+    const fn raw(self) -> Self {
+        Self { escape: false, ..self }
+    }
+
+    const fn multiline(self) -> Self {
+        Self { multiline: true, ..self }
+    }
+
+    const fn character(self) -> Self {
+        Self { character: true, ..self }
+    }
+
+    /// Where the string whose content starts at byte `from` of `text` ends,
+    /// and whether it is closed on this line.
+    fn end(&self, text: &str, from: usize) -> (usize, bool) {
+        let mut chars = text[from..].char_indices();
+        while let Some((offset, c)) = chars.next() {
+            if self.escape && c == '\\' {
+                chars.next();
+            } else if text[from + offset..].starts_with(self.close) {
+                return (from + offset + self.close.len(), true);
+            }
+        }
+        (text.len(), false)
+    }
+
+    fn opens(&self, rest: &str) -> bool {
+        let Some(after) = rest.strip_prefix(self.open) else {
+            return false;
+        };
+        if !self.character {
+            return true;
+        }
+        let mut chars = after.chars();
+        match chars.next() {
+            Some('\\') => after.get(1..).is_some_and(|escaped| escaped.chars().take(10).any(|c| c == '\'')),
+            Some(_) => chars.next() == Some('\''),
+            None => false,
+        }
+    }
+}
+
+/// How keys are told apart from values.
+#[derive(Copy, Clone, PartialEq, Eq)]
+pub enum Keys {
+    None,
+    /// `key = value` lines and `[table]` headers.
+    Toml,
+    /// Strings followed by a colon.
+    Json,
+}
+
+/// What the tokens of a programming or data language look like.
+pub struct Grammar {
+    line_comments: &'static [&'static str],
+    block_comment: Option<(&'static str, &'static str)>,
+    /// A line comment only starts at the beginning of a word, as `#` in
+    /// shell scripts, where `$#` is a variable.
+    comment_after_space: bool,
+    quotes: &'static [Quote],
+    keywords: &'static [&'static str],
+    types: &'static [&'static str],
+    constants: &'static [&'static str],
+    /// Lines starting with this character are directives.
+    directive: Option<char>,
+    /// Words starting with a capital letter are types.
+    capitalized_types: bool,
+    /// `$name` and `${...}` are variables.
+    variables: bool,
+    keys: Keys,
+}
+
+fn is_word(c: char) -> bool {
+    c.is_alphanumeric() || c == '_'
+}
+
+impl Grammar {
+    /// Adds the spans of `text`, a line starting in `state`, and returns
+    /// the state the next line starts in.
+    pub fn tokenize(&self, text: &str, state: State, spans: &mut Vec<(Range<usize>, TokenKind)>) -> State {
+        let mut position = 0;
+        match state {
+            State::BlockComment => {
+                let Some(end) = self.block_comment_end(text, 0) else {
+                    spans.push((0..text.len(), TokenKind::Comment));
+                    return State::BlockComment;
+                };
+                spans.push((0..end, TokenKind::Comment));
+                position = end;
+            },
+            State::String(index) => {
+                let Some(quote) = self.quotes.get(index) else {
+                    return State::Normal;
+                };
+                let (end, closed) = quote.end(text, 0);
+                spans.push((0..end, TokenKind::String));
+                if !closed {
+                    return state;
+                }
+                position = end;
+            },
+            State::Normal | State::CodeBlock => {
+                let trimmed = text.trim_start();
+                if self.directive.is_some_and(|directive| trimmed.starts_with(directive)) {
+                    spans.push((text.len() - trimmed.len()..text.len(), TokenKind::Directive));
+                    return State::Normal;
+                }
+                if self.keys == Keys::Toml {
+                    position = Self::toml_line_start(text, spans);
+                }
+            },
+        }
+        while let Some(c) = text[position..].chars().next() {
+            let rest = &text[position..];
+            let previous = text[..position].chars().next_back();
+            if self.starts_line_comment(rest, previous) {
+                spans.push((position..text.len(), TokenKind::Comment));
+                return State::Normal;
+            }
+            if let Some((open, _)) = self.block_comment.filter(|(open, _)| rest.starts_with(open)) {
+                let Some(end) = self.block_comment_end(text, position + open.len()) else {
+                    spans.push((position..text.len(), TokenKind::Comment));
+                    return State::BlockComment;
+                };
+                spans.push((position..end, TokenKind::Comment));
+                position = end;
+                continue;
+            }
+            if let Some((index, quote)) = self.quotes.iter().enumerate().find(|(_, quote)| quote.opens(rest)) {
+                let (end, closed) = quote.end(text, position + quote.open.len());
+                let is_key = self.keys == Keys::Json && text[end..].trim_start().starts_with(':');
+                spans.push((position..end, if is_key { TokenKind::Key } else { TokenKind::String }));
+                if !closed && quote.multiline {
+                    return State::String(index);
+                }
+                position = end;
+                continue;
+            }
+            if self.variables && c == '$' {
+                let end = Self::variable_end(text, position);
+                spans.push((position..end, TokenKind::Variable));
+                position = end;
+                continue;
+            }
+            if is_word(c) {
+                let is_number = c.is_ascii_digit();
+                let end = rest
+                    .char_indices()
+                    .find(|(offset, c)| {
+                        let decimal_point = is_number && *c == '.' && rest[offset + 1..].starts_with(|c: char| c.is_ascii_digit());
+                        !is_word(*c) && !decimal_point
+                    })
+                    .map_or(text.len(), |(offset, _)| position + offset);
+                if let Some(kind) = self.classify(&text[position..end]) {
+                    spans.push((position..end, kind));
+                }
+                position = end;
+                continue;
+            }
+            position += c.len_utf8();
+        }
+        State::Normal
+    }
+
+    fn starts_line_comment(&self, rest: &str, previous: Option<char>) -> bool {
+        self.line_comments.iter().any(|marker| rest.starts_with(marker))
+            && (!self.comment_after_space || previous.is_none_or(|c| c.is_whitespace() || c == ';'))
+    }
+
+    fn block_comment_end(&self, text: &str, from: usize) -> Option<usize> {
+        let (_, close) = self.block_comment?;
+        text[from..].find(close).map(|offset| from + offset + close.len())
+    }
+
+    fn classify(&self, word: &str) -> Option<TokenKind> {
+        if word.starts_with(|c: char| c.is_ascii_digit()) {
+            Some(TokenKind::Number)
+        } else if self.keywords.contains(&word) {
+            Some(TokenKind::Keyword)
+        } else if self.types.contains(&word) || self.capitalized_types && word.starts_with(char::is_uppercase) {
+            Some(TokenKind::Type)
+        } else if self.constants.contains(&word) {
+            Some(TokenKind::Constant)
+        } else {
+            None
+        }
+    }
+
+    /// The end of the variable starting with the `$` at `from`: a name, a
+    /// `${...}` expansion, or a single special character such as `$?`.
+    fn variable_end(text: &str, from: usize) -> usize {
+        let rest = &text[from + 1..];
+        if rest.starts_with('{') {
+            return rest.find('}').map_or(text.len(), |offset| from + offset + 2);
+        }
+        match rest.chars().next() {
+            Some(c) if is_word(c) && !c.is_ascii_digit() => {
+                rest.find(|c: char| !is_word(c)).map_or(text.len(), |offset| from + 1 + offset)
+            },
+            Some(c) if c.is_ascii_digit() || "@*#?$!-".contains(c) => from + 1 + c.len_utf8(),
+            _ => from + 1,
+        }
+    }
+
+    /// Marks a `[table]` header or the key of a `key = value` line, and
+    /// returns where the rest of the line starts.
+    fn toml_line_start(text: &str, spans: &mut Vec<(Range<usize>, TokenKind)>) -> usize {
+        let start = text.len() - text.trim_start().len();
+        let rest = &text[start..];
+        if rest.starts_with('[') {
+            let end = rest.rfind(']').map_or(text.len(), |offset| start + offset + 1);
+            spans.push((start..end, TokenKind::Type));
+            return end;
+        }
+        match rest.find('=') {
+            Some(offset) if !rest[..offset].trim().is_empty() && !rest[..offset].contains(['"', '\'', '#']) => {
+                spans.push((start..start + rest[..offset].trim_end().len(), TokenKind::Key));
+                start + offset
+            },
+            _ => start,
+        }
+    }
+}
+
+pub const RUST: Grammar = Grammar {
+    line_comments: &["//"],
+    block_comment: Some(("/*", "*/")),
+    comment_after_space: false,
+    quotes: &[
+        Quote::new("r#\"", "\"#").raw().multiline(),
+        Quote::new("r\"", "\"").raw().multiline(),
+        Quote::new("\"", "\"").multiline(),
+        Quote::new("'", "'").character(),
+    ],
+    keywords: &[
+        "as", "async", "await", "break", "const", "continue", "crate", "dyn", "else", "enum", "extern", "fn", "for",
+        "if", "impl", "in", "let", "loop", "match", "mod", "move", "mut", "pub", "ref", "return", "self", "static",
+        "struct", "super", "trait", "type", "unsafe", "use", "where", "while",
+    ],
+    types: &[
+        "bool", "char", "str", "u8", "u16", "u32", "u64", "u128", "usize", "i8", "i16", "i32", "i64", "i128",
+        "isize", "f32", "f64",
+    ],
+    constants: &["true", "false"],
+    directive: Some('#'),
+    capitalized_types: true,
+    variables: false,
+    keys: Keys::None,
+};
+
+pub const PYTHON: Grammar = Grammar {
+    line_comments: &["#"],
+    block_comment: None,
+    comment_after_space: false,
+    quotes: &[
+        Quote::new("\"\"\"", "\"\"\"").multiline(),
+        Quote::new("'''", "'''").multiline(),
+        Quote::new("\"", "\""),
+        Quote::new("'", "'"),
+    ],
+    keywords: &[
+        "and", "as", "assert", "async", "await", "break", "class", "continue", "def", "del", "elif", "else",
+        "except", "finally", "for", "from", "global", "if", "import", "in", "is", "lambda", "nonlocal", "not", "or",
+        "pass", "raise", "return", "try", "while", "with", "yield",
+    ],
+    types: &["bool", "bytes", "dict", "float", "int", "list", "object", "set", "str", "tuple"],
+    constants: &["True", "False", "None"],
+    directive: Some('@'),
+    capitalized_types: false,
+    variables: false,
+    keys: Keys::None,
+};
+
+pub const C: Grammar = Grammar {
+    line_comments: &["//"],
+    block_comment: Some(("/*", "*/")),
+    comment_after_space: false,
+    quotes: &[Quote::new("\"", "\""), Quote::new("'", "'").character()],
+    keywords: &[
+        "break", "case", "const", "continue", "default", "do", "else", "enum", "extern", "for", "goto", "if",
+        "inline", "register", "return", "sizeof", "static", "struct", "switch", "typedef", "union", "volatile",
+        "while",
+    ],
+    types: &[
+        "bool", "char", "double", "float", "int", "long", "short", "signed", "unsigned", "void", "size_t",
+        "int8_t", "int16_t", "int32_t", "int64_t", "uint8_t", "uint16_t", "uint32_t", "uint64_t",
+    ],
+    constants: &["NULL", "true", "false"],
+    directive: Some('#'),
+    capitalized_types: false,
+    variables: false,
+    keys: Keys::None,
+};
+
+pub const JSON: Grammar = Grammar {
+    line_comments: &[],
+    block_comment: None,
+    comment_after_space: false,
+    quotes: &[Quote::new("\"", "\"")],
+    keywords: &[],
+    types: &[],
+    constants: &["true", "false", "null"],
+    directive: None,
+    capitalized_types: false,
+    variables: false,
+    keys: Keys::Json,
+};
+
+pub const TOML: Grammar = Grammar {
+    line_comments: &["#"],
+    block_comment: None,
+    comment_after_space: false,
+    quotes: &[
+        Quote::new("\"\"\"", "\"\"\"").multiline(),
+        Quote::new("'''", "'''").raw().multiline(),
+        Quote::new("\"", "\""),
+        Quote::new("'", "'").raw(),
+    ],
+    keywords: &[],
+    types: &[],
+    constants: &["true", "false", "inf", "nan"],
+    directive: None,
+    capitalized_types: false,
+    variables: false,
+    keys: Keys::Toml,
+};
+
+pub const SHELL: Grammar = Grammar {
+    line_comments: &["#"],
+    block_comment: None,
+    comment_after_space: true,
+    quotes: &[Quote::new("\"", "\"").multiline(), Quote::new("'", "'").raw().multiline()],
+    keywords: &[
+        "case", "do", "done", "elif", "else", "esac", "export", "fi", "for", "function", "if", "in", "local",
+        "readonly", "return", "select", "then", "until", "while",
+    ],
+    types: &[],
+    constants: &["true", "false"],
+    directive: None,
+    capitalized_types: false,
+    variables: true,
+    keys: Keys::None,
+};
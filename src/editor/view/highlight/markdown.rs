@@ -0,0 +1,83 @@
+use std::ops::Range;
+use super::{State, TokenKind};
+
+/// Adds the spans of a Markdown line starting in `state` and returns the
+/// state the next line starts in. Markdown is mostly about whole lines, so
+/// it has a tokenizer of its own rather than a `Grammar`.
+pub fn tokenize(text: &str, state: State, spans: &mut Vec<(Range<usize>, TokenKind)>) -> State {
+    let indent = text.len() - text.trim_start().len();
+    let rest = &text[indent..];
+    if rest.starts_with("```") || rest.starts_with("~~~") {
+        spans.push((0..text.len(), TokenKind::Code));
+        return if state == State::CodeBlock { State::Normal } else { State::CodeBlock };
+    }
+    if state == State::CodeBlock {
+        spans.push((0..text.len(), TokenKind::Code));
+        return State::CodeBlock;
+    }
+    let hashes = rest.chars().take_while(|c| *c == '#').count();
+    if (1..=6).contains(&hashes) && rest[hashes..].chars().next().is_none_or(char::is_whitespace) {
+        spans.push((indent..text.len(), TokenKind::Heading));
+        return State::Normal;
+    }
+    if rest.starts_with('>') {
+        spans.push((indent..text.len(), TokenKind::Comment));
+        return State::Normal;
+    }
+    let marker = list_marker(rest);
+    if marker > 0 {
+        spans.push((indent..indent + marker, TokenKind::Keyword));
+    }
+    inline(text, indent + marker, spans);
+    State::Normal
+}
+
+/// The length of the `-`, `*`, `+` or `1.` that starts a list item, if any.
+fn list_marker(text: &str) -> usize {
+    let digits = text.chars().take_while(char::is_ascii_digit).count();
+    let marker = if digits > 0 && text[digits..].starts_with(['.', ')']) {
+        digits + 1
+    } else if text.starts_with(['-', '*', '+']) {
+        1
+    } else {
+        return 0;
+    };
+    if text[marker..].starts_with(' ') { marker } else { 0 }
+}
+
+/// Code spans, emphasis and links within a line.
+fn inline(text: &str, from: usize, spans: &mut Vec<(Range<usize>, TokenKind)>) {
+    let mut position = from;
+    while let Some(c) = text[position..].chars().next() {
+        let rest = &text[position..];
+        let found = match c {
+            '`' => closing(rest, "`").map(|end| (end, TokenKind::Code)),
+            '_' if text[..position].chars().next_back().is_some_and(char::is_alphanumeric) => None,
+            '*' | '_' => {
+                let delimiter = if rest[1..].starts_with(c) { &rest[..2] } else { &rest[..1] };
+                closing(rest, delimiter).map(|end| (end, TokenKind::Emphasis))
+            },
+            '[' => rest
+                .find("](")
+                .and_then(|middle| rest[middle..].find(')').map(|end| middle + end + 1))
+                .map(|end| (end, TokenKind::Link)),
+            _ => None,
+        };
+        if let Some((end, kind)) = found {
+            spans.push((position..position + end, kind));
+            position += end;
+        } else {
+            position += c.len_utf8();
+        }
+    }
+}
+
+/// The end of a span of `text` opened by `delimiter` and closed by the next
+/// one, if it is closed on this line and not empty.
+fn closing(text: &str, delimiter: &str) -> Option<usize> {
+    let content = &text[delimiter.len()..];
+    content
+        .find(delimiter)
+        .filter(|offset| *offset > 0)
+        .map(|offset| delimiter.len() + offset + delimiter.len())
+}
@@ -0,0 +1,87 @@
+/// How the indent of a new line follows from the line before it, and which
+/// lines close a block and go back one level when typed. Each filetype
+/// names its rules in `Filetype::indent_rules`; a language with other block
+/// syntax gets a table of its own here.
+pub struct IndentRules {
+    /// A line ending in one of these opens a block.
+    open_ends: &'static [&'static str],
+    /// A line whose last word, after a space or `;`, is one of these opens
+    /// a block.
+    open_words: &'static [&'static str],
+    /// Lines starting with this are comments, which open nothing.
+    line_comment: Option<&'static str>,
+    /// A line that is just one of these closes a block.
+    closers: &'static [&'static str],
+    /// A line starting with one of these closes a block, whatever follows.
+    closer_starts: &'static [&'static str],
+    /// A line starting with one of these closes a block if it also opens
+    /// one, as `elif x:` does in Python.
+    closer_heads: &'static [&'static str],
+}
+
+impl IndentRules {
+    /// Whether the line after `line` is indented one level deeper.
+    pub fn opens_block(&self, line: &str) -> bool {
+        let line = line.trim_end();
+        if self.line_comment.is_some_and(|comment| line.trim_start().starts_with(comment)) {
+            return false;
+        }
+        self.open_ends.iter().any(|end| line.ends_with(end))
+            || line
+                .rsplit(|c: char| c.is_whitespace() || c == ';')
+                .next()
+                .is_some_and(|word| self.open_words.contains(&word))
+    }
+
+    /// Whether a line starting with `text`, which was just typed, closes
+    /// the block it is in and belongs one level further out.
+    pub fn closes_block(&self, text: &str) -> bool {
+        let text = text.trim();
+        self.closers.contains(&text)
+            || self.closer_starts.iter().any(|start| text.starts_with(start))
+            || (self.closer_heads.iter().any(|head| text.starts_with(head)) && self.opens_block(text))
+    }
+}
+
+/// Only the indent of the line before is kept.
+pub const PLAIN: IndentRules = IndentRules {
+    open_ends: &[],
+    open_words: &[],
+    line_comment: None,
+    closers: &[],
+    closer_starts: &[],
+    closer_heads: &[],
+};
+
+/// Blocks are opened by `{`, `(` or `[` at the end of a line and closed by
+/// a line starting with the matching bracket, as `});` or `} else {`.
+pub const BRACKETS: IndentRules = IndentRules {
+    open_ends: &["{", "(", "["],
+    open_words: &[],
+    line_comment: None,
+    closers: &[],
+    closer_starts: &["}", ")", "]"],
+    closer_heads: &[],
+};
+
+/// Blocks are opened by a `:` at the end of a line; `else:`, `elif`,
+/// `except` and `finally:` belong with the `if` or `try` above.
+pub const PYTHON: IndentRules = IndentRules {
+    open_ends: &[":"],
+    open_words: &[],
+    line_comment: Some("#"),
+    closers: &["else:", "finally:"],
+    closer_starts: &[],
+    closer_heads: &["elif ", "except"],
+};
+
+/// Blocks are opened by `do`, `then`, `else` or `{` and closed by `done`,
+/// `fi`, `else` or `}`.
+pub const SHELL: IndentRules = IndentRules {
+    open_ends: &["{"],
+    open_words: &["do", "then", "else"],
+    line_comment: None,
+    closers: &["done", "fi", "else", "elif", "esac", "}"],
+    closer_starts: &[],
+    closer_heads: &[],
+};
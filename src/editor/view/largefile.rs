@@ -0,0 +1,212 @@
+use std::fs::File;
+use std::io::{BufRead, BufReader, Error, Read, Seek, SeekFrom};
+use std::ops::Range;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex, PoisonError};
+use std::thread::{self, JoinHandle};
+use super::line::Line;
+use super::Location;
+
+const INDEX_CHUNK_SIZE: usize = 1 << 20;
+const WINDOW_MARGIN: usize = 1000;
+
+#[derive(Default)]
+struct LineIndex {
+    offsets: Vec<u64>,
+    complete: bool,
+}
+
+/// A file too large to keep in memory. Line start offsets are indexed by a
+/// background thread, and only a window of lines around what is being looked
+/// at is actually read and turned into `Line`s.
+pub struct LargeFile {
+    path: String,
+    index: Arc<Mutex<LineIndex>>,
+    window_start: usize,
+    window: Vec<Line>,
+}
+
+impl LargeFile {
+    pub fn open(path: &str) -> Result<Self, Error> {
+        let file = File::open(path)?;
+        let index = Arc::new(Mutex::new(LineIndex::default()));
+        let shared = Arc::clone(&index);
+        thread::spawn(move || Self::build_index(file, &shared));
+        Ok(Self {
+            path: path.to_string(),
+            index,
+            window_start: 0,
+            window: Vec::new(),
+        })
+    }
+
+    fn build_index(mut file: File, index: &Mutex<LineIndex>) {
+        let mut chunk = vec![0; INDEX_CHUNK_SIZE];
+        let mut position: u64 = 0;
+        let mut at_line_start = true;
+        loop {
+            let read = match file.read(&mut chunk) {
+                Ok(0) | Err(_) => break,
+                Ok(read) => read,
+            };
+            let mut found = Vec::new();
+            for (i, byte) in chunk[..read].iter().enumerate() {
+                if at_line_start {
+                    #[allow(clippy::as_conversions)]
+                    found.push(position + i as u64);
+                }
+                at_line_start = *byte == b'\n';
+            }
+            #[allow(clippy::as_conversions)]
+            {
+                position += read as u64;
+            }
+            index.lock().unwrap_or_else(PoisonError::into_inner).offsets.extend(found);
+        }
+        index.lock().unwrap_or_else(PoisonError::into_inner).complete = true;
+    }
+
+    pub fn line_count(&self) -> usize {
+        self.index.lock().unwrap_or_else(PoisonError::into_inner).offsets.len()
+    }
+
+    pub fn is_indexing(&self) -> bool {
+        !self.index.lock().unwrap_or_else(PoisonError::into_inner).complete
+    }
+
+    fn offset_of(index: &Mutex<LineIndex>, line_index: usize) -> Option<u64> {
+        index
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .offsets
+            .get(line_index)
+            .copied()
+    }
+
+    pub fn line(&self, line_index: usize) -> Option<&Line> {
+        line_index
+            .checked_sub(self.window_start)
+            .and_then(|i| self.window.get(i))
+    }
+
+    /// Makes sure every line in `range` that exists is held in the window,
+    /// reading it from disk (with some margin around it) if it is not.
+    pub fn ensure_loaded(&mut self, range: Range<usize>) {
+        let count = self.line_count();
+        let end = range.end.min(count);
+        let window_end = self.window_start + self.window.len();
+        if range.start >= end || (range.start >= self.window_start && end <= window_end) {
+            return;
+        }
+        let start = range.start.saturating_sub(WINDOW_MARGIN);
+        let end = end.saturating_add(WINDOW_MARGIN).min(count);
+        if let Ok(lines) = self.read_lines(start, end - start) {
+            self.window_start = start;
+            self.window = lines.iter().map(|line| Line::from(line)).collect();
+        }
+    }
+
+    fn reader_at(path: &str, index: &Mutex<LineIndex>, line_index: usize) -> Result<BufReader<File>, Error> {
+        let offset = Self::offset_of(index, line_index).unwrap_or_default();
+        let mut file = File::open(path)?;
+        file.seek(SeekFrom::Start(offset))?;
+        Ok(BufReader::new(file))
+    }
+
+    fn read_lines(&self, start: usize, count: usize) -> Result<Vec<String>, Error> {
+        let mut reader = Self::reader_at(&self.path, &self.index, start)?;
+        let mut lines = Vec::with_capacity(count);
+        let mut raw = Vec::new();
+        for _ in 0..count {
+            raw.clear();
+            if reader.read_until(b'\n', &mut raw)? == 0 {
+                break;
+            }
+            lines.push(Self::decode(&raw));
+        }
+        Ok(lines)
+    }
+
+    fn decode(raw: &[u8]) -> String {
+        let raw = raw.strip_suffix(b"\n").unwrap_or(raw);
+        let raw = raw.strip_suffix(b"\r").unwrap_or(raw);
+        String::from_utf8_lossy(raw).into_owned()
+    }
+
+    /// Starts streaming the file from `from` looking for `pattern`,
+    /// wrapping around to the start once the end of the indexed lines is
+    /// reached. The file is read on a thread of its own, as going through
+    /// all of it takes a while.
+    pub fn search(&self, pattern: &str, from: Location) -> BackgroundSearch {
+        let path = self.path.clone();
+        let index = Arc::clone(&self.index);
+        let pattern = pattern.to_string();
+        let cancelled = Arc::new(AtomicBool::new(false));
+        let stop = Arc::clone(&cancelled);
+        let handle = thread::spawn(move || {
+            let count = index.lock().unwrap_or_else(PoisonError::into_inner).offsets.len();
+            if count == 0 {
+                return None;
+            }
+            let from_line = from.line_index.min(count - 1);
+            let search = |start, end, skip_until| Self::search_lines(&path, &index, &stop, &pattern, start..end, skip_until);
+            search(from_line, count, Some(from.grapheme_index)).or_else(|| search(0, from_line + 1, None))
+        });
+        BackgroundSearch { handle: Some(handle), cancelled }
+    }
+
+    fn search_lines(
+        path: &str,
+        index: &Mutex<LineIndex>,
+        cancelled: &AtomicBool,
+        pattern: &str,
+        lines: Range<usize>,
+        skip_until: Option<usize>,
+    ) -> Option<Location> {
+        let mut reader = Self::reader_at(path, index, lines.start).ok()?;
+        let mut raw = Vec::new();
+        for line_index in lines.clone() {
+            if cancelled.load(Ordering::Relaxed) {
+                return None;
+            }
+            raw.clear();
+            if reader.read_until(b'\n', &mut raw).ok()? == 0 {
+                break;
+            }
+            let text = Self::decode(&raw);
+            if !text.contains(pattern) {
+                continue;
+            }
+            let after = if line_index == lines.start { skip_until } else { None };
+            if let Some(grapheme_index) = Line::from(&text).find(pattern, after) {
+                return Some(Location { grapheme_index, line_index });
+            }
+        }
+        None
+    }
+}
+
+/// A search through a `LargeFile` running in the background. Dropping it
+/// stops the search.
+pub struct BackgroundSearch {
+    handle: Option<JoinHandle<Option<Location>>>,
+    cancelled: Arc<AtomicBool>,
+}
+
+impl BackgroundSearch {
+    /// Whether the search is over, so that `wait` returns right away.
+    pub fn is_done(&self) -> bool {
+        self.handle.as_ref().is_none_or(JoinHandle::is_finished)
+    }
+
+    /// Waits for the search to finish, and returns what it found.
+    pub fn wait(mut self) -> Option<Location> {
+        self.handle.take()?.join().ok().flatten()
+    }
+}
+
+impl Drop for BackgroundSearch {
+    fn drop(&mut self) {
+        self.cancelled.store(true, Ordering::Relaxed);
+    }
+}
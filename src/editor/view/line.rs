@@ -2,7 +2,7 @@ use std::{fmt, ops::Range};
 use unicode_segmentation::UnicodeSegmentation;
 use unicode_width::UnicodeWidthStr;
 
-#[derive(Default)]
+#[derive(Default, Clone)]
 pub struct Line {
     fragments: Vec<TextFragment>,
 }
@@ -11,17 +11,25 @@ pub struct Line {
 enum GraphemeWidth {
     Half,
     Full,
+    /// Reaches up to the next tabstop.
+    Tab,
 }
 
 impl GraphemeWidth {
-    const fn saturating_add(self, other: usize) -> usize {
+    /// How many columns the grapheme takes up when drawn at `column`.
+    const fn at(self, column: usize, tabstop: usize) -> usize {
         match self {
-            Self::Half => other.saturating_add(1),
-            Self::Full => other.saturating_add(2),
+            Self::Half => 1,
+            Self::Full => 2,
+            Self::Tab => {
+                let tabstop = if tabstop == 0 { 1 } else { tabstop };
+                tabstop - column % tabstop
+            },
         }
     }
 }
 
+#[derive(Clone)]
 struct TextFragment {
     grapheme: String,
     rendered_width: GraphemeWidth,
@@ -30,6 +38,7 @@ struct TextFragment {
 
 
 impl Line {
+    #[must_use]
     pub fn from(line_str: &str) -> Self {
         let fragments = Self::str_to_fragments(line_str);
         Self { fragments }
@@ -49,7 +58,10 @@ impl Line {
                             };
                             (None, rendered_width)
                         },
-                        |replacement| (Some(replacement), GraphemeWidth::Half),
+                        |replacement| {
+                            let rendered_width = if grapheme == "\t" { GraphemeWidth::Tab } else { GraphemeWidth::Half };
+                            (Some(replacement), rendered_width)
+                        },
                     );
                 TextFragment {
                     grapheme: grapheme.to_string(),
@@ -68,10 +80,9 @@ impl Line {
         _ if width > 0 && for_str.trim().is_empty() => Some('␣'),
         _ if width == 0 => {
             let mut chars = for_str.chars();
-                if let Some(ch) = chars.next() {
-                    if ch.is_control() && chars.next().is_none() {
-                        return Some('▯');
-                    }
+                if let Some(ch) = chars.next()
+                    && ch.is_control() && chars.next().is_none() {
+                    return Some('▯');
                 }
                 Some('·')
             }
@@ -79,19 +90,34 @@ impl Line {
         }
     }
 
-    pub fn get_visible_graphemes(&self, range: Range<usize>) -> String {
+    /// The width of each grapheme in turn, tabs depending on where they
+    /// start.
+    fn widths(&self, tabstop: usize) -> impl Iterator<Item = usize> + '_ {
+        let mut column = 0;
+        self.fragments.iter().map(move |fragment| {
+            let width = fragment.rendered_width.at(column, tabstop);
+            column += width;
+            width
+        })
+    }
+
+    #[must_use]
+    pub fn get_visible_graphemes(&self, range: Range<usize>, tabstop: usize) -> String {
         if range.start >= range.end {
             return String::new();
         }
         let mut result = String::new();
         let mut current_pos = 0;
-        for fragment in &self.fragments {
-            let fragment_end = fragment.rendered_width.saturating_add(current_pos);
+        for (fragment, width) in self.fragments.iter().zip(self.widths(tabstop)) {
+            let fragment_end = current_pos + width;
             if current_pos >= range.end {
                 break;
             }
             if fragment_end > range.start {
-                if fragment_end > range.end || current_pos < range.start {
+                if let GraphemeWidth::Tab = fragment.rendered_width {
+                    let visible = fragment_end.min(range.end) - current_pos.max(range.start);
+                    result.push_str(&" ".repeat(visible));
+                } else if fragment_end > range.end || current_pos < range.start {
                     result.push('⋯');
                 } else if let Some(char) = fragment.replacement {
                     result.push(char);
@@ -104,19 +130,125 @@ impl Line {
         result
     }
 
+    /// The text of the graphemes in `range`, as it is in the file.
+    #[must_use]
+    pub fn text(&self, range: Range<usize>) -> String {
+        self.fragments
+            .iter()
+            .take(range.end)
+            .skip(range.start)
+            .map(|fragment| fragment.grapheme.as_str())
+            .collect()
+    }
+
+    #[must_use]
     pub fn grapheme_count(&self) -> usize{
         self.fragments.len()
     }
 
-    pub fn width_until(&self, grapheme_index: usize) -> usize {
-        self.fragments
-            .iter()
-            .take(grapheme_index)
-            .map(|fragment| match fragment.rendered_width {
-                GraphemeWidth::Half => 1,
-                GraphemeWidth::Full => 2,
+    #[must_use]
+    pub fn width_until(&self, grapheme_index: usize, tabstop: usize) -> usize {
+        self.widths(tabstop).take(grapheme_index).sum()
+    }
+
+    /// The grapheme drawn at display column `column`, or the grapheme count if
+    /// the line is not that wide.
+    #[must_use]
+    pub fn grapheme_index_at(&self, column: usize, tabstop: usize) -> usize {
+        let mut end = 0;
+        self.widths(tabstop)
+            .position(|width| {
+                end += width;
+                end > column
             })
-            .sum()
+            .unwrap_or(self.fragments.len())
+    }
+
+    /// How many graphemes of spaces and tabs the line starts with, and how
+    /// wide they are.
+    #[must_use]
+    pub fn indent(&self, tabstop: usize) -> (usize, usize) {
+        let count = self
+            .fragments
+            .iter()
+            .take_while(|fragment| fragment.grapheme == " " || fragment.grapheme == "\t")
+            .count();
+        (count, self.width_until(count, tabstop))
+    }
+
+    /// Splits the line into screen rows: the first `width` columns wide, the
+    /// rest `continuation_width`. Returns the grapheme index each row starts
+    /// at. A wide grapheme that does not fit at the end of a row moves to the
+    /// next one whole. With `at_word`, rows end after whitespace where
+    /// possible.
+    #[must_use]
+    pub fn wrap(&self, width: usize, continuation_width: usize, at_word: bool, tabstop: usize) -> Vec<usize> {
+        let widths: Vec<usize> = self.widths(tabstop).collect();
+        let columns: Vec<usize> = std::iter::once(0)
+            .chain(widths.iter().scan(0, |column, width| {
+                *column += width;
+                Some(*column)
+            }))
+            .collect();
+        let mut starts = vec![0];
+        let mut row_width = width.max(1);
+        let mut used = 0;
+        let mut last_break = None;
+        let mut index = 0;
+        while let (Some(fragment), Some(fragment_width)) = (self.fragments.get(index), widths.get(index)) {
+            if used > 0 && used + fragment_width > row_width {
+                row_width = continuation_width.max(1);
+                let start = last_break
+                    .filter(|start| columns[index] - columns[*start] + fragment_width <= row_width)
+                    .unwrap_or(index);
+                starts.push(start);
+                used = columns[index] - columns[start];
+                last_break = None;
+                continue;
+            }
+            used += fragment_width;
+            if at_word && fragment.grapheme.trim().is_empty() {
+                last_break = Some(index + 1);
+            }
+            index += 1;
+        }
+        starts
+    }
+
+    /// Returns the grapheme index of the first match of `pattern` that starts
+    /// strictly after the grapheme at `after`, or anywhere if `after` is `None`.
+    #[must_use]
+    pub fn find(&self, pattern: &str, after: Option<usize>) -> Option<usize> {
+        if pattern.is_empty() {
+            return None;
+        }
+        let text = self.to_string();
+        let mut byte_index = 0;
+        for (grapheme_index, fragment) in self.fragments.iter().enumerate() {
+            if after.is_none_or(|after| grapheme_index > after)
+                && text[byte_index..].starts_with(pattern) {
+                return Some(grapheme_index);
+            }
+            byte_index += fragment.grapheme.len();
+        }
+        None
+    }
+
+    /// The graphemes around the one at `grapheme_index` that are of the same
+    /// kind as it: letters, digits and underscores, whitespace, or anything
+    /// else. Empty past the end of the line.
+    #[must_use]
+    pub fn word_at(&self, grapheme_index: usize) -> Range<usize> {
+        let kind = |fragment: &TextFragment| {
+            let c = fragment.grapheme.chars().next().unwrap_or_default();
+            if c.is_alphanumeric() || c == '_' { 0 } else if c.is_whitespace() { 1 } else { 2 }
+        };
+        let Some(clicked) = self.fragments.get(grapheme_index).map(kind) else {
+            return grapheme_index..grapheme_index;
+        };
+        let start = self.fragments[..grapheme_index].iter().rposition(|fragment| kind(fragment) != clicked).map_or(0, |index| index + 1);
+        let end = self.fragments[grapheme_index..].iter().position(|fragment| kind(fragment) != clicked).map_or(self.fragments.len(), |index| grapheme_index + index);
+        start..end
     }
 
     pub fn insert_char(&mut self, character: char, grapheme_index: usize) {
@@ -151,6 +283,7 @@ impl Line {
         self.fragments = Self::str_to_fragments(&concat);
     }
 
+    #[must_use]
     pub fn split(&mut self, grapheme_index: usize) -> Self {
         if grapheme_index > self.fragments.len() {
             return Self::default();
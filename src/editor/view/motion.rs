@@ -0,0 +1,54 @@
+use super::buffer::Buffer;
+use super::line::Line;
+use super::Location;
+
+/// A move of the caret that only depends on the text, not on how it is laid
+/// out on screen. Columns are counted in graphemes, so a motion never ends
+/// up inside one.
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum Motion {
+    /// One grapheme back, stopping at the start of the line.
+    Left,
+    /// One grapheme on, stopping just past the end of the line.
+    Right,
+    /// That many lines up, keeping the column where the line is long enough.
+    Up(usize),
+    /// That many lines down, keeping the column where the line is long
+    /// enough. The line just past the last one can be moved to, to add text
+    /// there.
+    Down(usize),
+    LineStart,
+    /// Just past the last grapheme of the line.
+    LineEnd,
+    /// The first grapheme of the line that is not a space or a tab.
+    FirstNonBlank,
+}
+
+impl Motion {
+    /// Where the motion goes from `from` in `buffer`.
+    #[must_use]
+    pub fn apply(self, buffer: &Buffer, from: Location) -> Location {
+        let line_length = |line_index| buffer.line(line_index).map_or(0, Line::grapheme_count);
+        let Location { grapheme_index, line_index } = from;
+        match self {
+            Self::Left => Location { grapheme_index: grapheme_index.saturating_sub(1), line_index },
+            Self::Right => Location { grapheme_index: (grapheme_index + 1).min(line_length(line_index)), line_index },
+            Self::Up(step) => {
+                let line_index = line_index.saturating_sub(step);
+                Location { grapheme_index: grapheme_index.min(line_length(line_index)), line_index }
+            },
+            Self::Down(step) => {
+                let line_index = line_index.saturating_add(step).min(buffer.height());
+                Location { grapheme_index: grapheme_index.min(line_length(line_index)), line_index }
+            },
+            Self::LineStart => Location { grapheme_index: 0, line_index },
+            Self::LineEnd => Location { grapheme_index: line_length(line_index), line_index },
+            Self::FirstNonBlank => {
+                // The tab stop only changes how wide the indent is, not how
+                // many graphemes it has.
+                let indent = buffer.line(line_index).map_or(0, |line| line.indent(1).0);
+                Location { grapheme_index: indent, line_index }
+            },
+        }
+    }
+}
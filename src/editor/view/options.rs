@@ -0,0 +1,137 @@
+const DEFAULT_STATUSLINE: &str = "%M %k%R%=%<%f%m%r%= %y  %l/%L:%c  %p%%";
+
+/// Settings changed with `:set`. Each view has its own, and a new window
+/// starts out with those of the window it was split from.
+#[derive(Clone)]
+#[allow(clippy::struct_excessive_bools)]
+pub struct Options {
+    pub number: bool,
+    pub relativenumber: bool,
+    /// Long lines continue on the next screen row instead of scrolling
+    /// sideways.
+    pub wrap: bool,
+    /// Wrapped lines are broken after whitespace rather than mid-word.
+    pub linebreak: bool,
+    /// Shown at the start of every continuation row of a wrapped line.
+    pub showbreak: String,
+    /// How many columns a tab character spans.
+    pub tabstop: usize,
+    /// How many columns one level of indent is; the tabstop if zero.
+    pub shiftwidth: usize,
+    /// How many columns the Tab key moves in Insert mode and Backspace
+    /// takes back over spaces; if zero, Tab inserts a tab character, or
+    /// spaces up to the next tabstop with `expandtab`.
+    pub softtabstop: usize,
+    /// Indent is made of spaces rather than tab characters.
+    pub expandtab: bool,
+    /// A new line starts with the indent of the line before it.
+    pub autoindent: bool,
+    /// With `autoindent`, the indent also follows the blocks of the
+    /// buffer's language.
+    pub smartindent: bool,
+    /// What the status line shows; see `StatusBar` for the items.
+    pub statusline: String,
+    /// How many rows are kept on screen above and below the caret's.
+    pub scrolloff: usize,
+}
+
+impl Default for Options {
+    fn default() -> Self {
+        Self {
+            number: false,
+            relativenumber: false,
+            wrap: false,
+            linebreak: false,
+            showbreak: String::new(),
+            tabstop: 8,
+            shiftwidth: 2,
+            softtabstop: 2,
+            expandtab: true,
+            autoindent: true,
+            smartindent: true,
+            statusline: DEFAULT_STATUSLINE.to_string(),
+            scrolloff: 0,
+        }
+    }
+}
+
+impl Options {
+    /// Applies a `:set` argument such as `number norelativenumber`. A flag
+    /// is switched off with a `no` prefix and toggled with an `inv` prefix or
+    /// a trailing `!`; other options are given a value with `name=value`.
+    /// A space within a value is escaped as `\ `.
+    pub fn set(&mut self, argument: &str) -> Result<(), String> {
+        for item in split_items(argument) {
+            self.set_one(&item)?;
+        }
+        Ok(())
+    }
+
+    pub const fn shiftwidth(&self) -> usize {
+        if self.shiftwidth == 0 { self.tabstop } else { self.shiftwidth }
+    }
+
+    fn set_one(&mut self, item: &str) -> Result<(), String> {
+        let unknown = || format!("Unknown option: {item}");
+        if let Some((name, value)) = item.split_once('=') {
+            let number = || value.parse::<usize>().map_err(|_| format!("Invalid argument: {item}"));
+            match name {
+                "showbreak" | "sbr" => self.showbreak = value.to_string(),
+                "statusline" | "stl" => self.statusline = value.to_string(),
+                "tabstop" | "ts" => match number()? {
+                    0 => return Err(format!("Invalid argument: {item}")),
+                    tabstop => self.tabstop = tabstop,
+                },
+                "shiftwidth" | "sw" => self.shiftwidth = number()?,
+                "softtabstop" | "sts" => self.softtabstop = number()?,
+                "scrolloff" | "so" => self.scrolloff = number()?,
+                _ => return Err(unknown()),
+            }
+        } else if let Some(name) = item.strip_suffix('!').or_else(|| item.strip_prefix("inv")) {
+            let flag = self.flag(name).ok_or_else(unknown)?;
+            *flag = !*flag;
+        } else if let Some(flag) = self.flag(item) {
+            *flag = true;
+        } else {
+            let name = item.strip_prefix("no").ok_or_else(unknown)?;
+            *self.flag(name).ok_or_else(unknown)? = false;
+        }
+        Ok(())
+    }
+
+    fn flag(&mut self, name: &str) -> Option<&mut bool> {
+        match name {
+            "number" | "nu" => Some(&mut self.number),
+            "relativenumber" | "rnu" => Some(&mut self.relativenumber),
+            "wrap" => Some(&mut self.wrap),
+            "linebreak" | "lbr" => Some(&mut self.linebreak),
+            "expandtab" | "et" => Some(&mut self.expandtab),
+            "autoindent" | "ai" => Some(&mut self.autoindent),
+            "smartindent" | "si" => Some(&mut self.smartindent),
+            _ => None,
+        }
+    }
+}
+
+/// The items of a `:set` argument, split at whitespace that is not escaped
+/// with a backslash.
+fn split_items(argument: &str) -> Vec<String> {
+    let mut items = Vec::new();
+    let mut item = String::new();
+    let mut chars = argument.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => item.push(chars.next().unwrap_or(c)),
+            c if c.is_whitespace() => {
+                if !item.is_empty() {
+                    items.push(std::mem::take(&mut item));
+                }
+            },
+            c => item.push(c),
+        }
+    }
+    if !item.is_empty() {
+        items.push(item);
+    }
+    items
+}
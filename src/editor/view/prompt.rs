@@ -0,0 +1,18 @@
+/// What a pending prompt is asking about, along with whatever its answers
+/// need to act on.
+pub enum PromptKind {
+    Recovery { swap_lines: Vec<String> },
+    FileChanged { write_after: bool },
+    Info,
+}
+
+/// The answer given when the prompt is dismissed with Escape.
+pub const ESCAPE: char = '\u{1b}';
+
+/// A question the view asks before carrying on, answered with a single key.
+/// While it is pending the view shows `preview`, if any, instead of the buffer.
+pub struct Prompt {
+    pub kind: PromptKind,
+    pub message: String,
+    pub preview: Vec<String>,
+}
@@ -0,0 +1,115 @@
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::fs::{read_to_string, remove_file, write, File};
+use std::io::{BufWriter, Error, Write};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+use super::line::Line;
+
+/// Minimum time between two writes of the swap file while editing.
+const SWAP_INTERVAL: Duration = Duration::from_secs(4);
+
+thread_local! {
+    /// What each swap file should hold, kept apart from the buffers so that
+    /// it can still be written when a panic leaves one of them borrowed.
+    static PENDING: RefCell<HashMap<PathBuf, String>> = RefCell::new(HashMap::new());
+}
+
+/// The journal of a buffer's unsaved contents, kept next to the file as
+/// `.name.swp` so the edits survive the terminal or vih dying.
+pub struct Swap {
+    path: PathBuf,
+    written_revision: Option<u64>,
+    written_at: Option<Instant>,
+    pending_revision: Option<u64>,
+}
+
+impl Swap {
+    pub fn new(filename: &str) -> Self {
+        Self {
+            path: Self::path_for(filename),
+            written_revision: None,
+            written_at: None,
+            pending_revision: None,
+        }
+    }
+
+    pub fn path_for(filename: &str) -> PathBuf {
+        let path = Path::new(filename);
+        let name = path.file_name().map_or_else(String::new, |name| name.to_string_lossy().into_owned());
+        path.with_file_name(format!(".{name}.swp"))
+    }
+
+    /// Returns the contents of a swap file left behind for `filename`.
+    pub fn read_existing(filename: &str) -> Option<String> {
+        read_to_string(Self::path_for(filename)).ok()
+    }
+
+    pub fn is_due(&self, revision: u64) -> bool {
+        self.written_revision != Some(revision)
+            && self.written_at.is_none_or(|at| at.elapsed() >= SWAP_INTERVAL)
+    }
+
+    pub fn write(&mut self, revision: u64, lines: &[Line]) -> Result<(), Error> {
+        self.write_lines(lines)?;
+        self.written_revision = Some(revision);
+        self.written_at = Some(Instant::now());
+        Ok(())
+    }
+
+    /// Keeps `lines` as what the swap file should hold, for `flush_pending`
+    /// to write should vih die before the next `write`.
+    pub fn keep(&mut self, revision: u64, lines: &[Line]) {
+        if self.pending_revision == Some(revision) {
+            return;
+        }
+        let mut contents = String::new();
+        for line in lines {
+            contents.push_str(&line.to_string());
+            contents.push('\n');
+        }
+        let path = self.path.clone();
+        let _ = PENDING.try_with(|pending| pending.borrow_mut().insert(path, contents));
+        self.pending_revision = Some(revision);
+    }
+
+    /// Writes `lines` out unless `revision` already was, whether or not the
+    /// swap file is due. For when vih is about to die.
+    pub fn flush(&self, revision: u64, lines: &[Line]) -> Result<(), Error> {
+        self.forget_pending();
+        if self.written_revision == Some(revision) {
+            return Ok(());
+        }
+        self.write_lines(lines)
+    }
+
+    /// Writes what was last kept for every swap file not flushed since,
+    /// which are those of the buffers a panic left out of reach.
+    pub fn flush_pending() {
+        let _ = PENDING.try_with(|pending| {
+            let Ok(mut pending) = pending.try_borrow_mut() else { return };
+            for (path, contents) in pending.drain() {
+                let _ = write(path, contents);
+            }
+        });
+    }
+
+    fn forget_pending(&self) {
+        let _ = PENDING.try_with(|pending| pending.borrow_mut().remove(&self.path));
+    }
+
+    fn write_lines(&self, lines: &[Line]) -> Result<(), Error> {
+        let mut file = BufWriter::new(File::create(&self.path)?);
+        for line in lines {
+            writeln!(file, "{line}")?;
+        }
+        file.flush()
+    }
+
+    pub fn remove(&mut self) {
+        let _ = remove_file(&self.path);
+        self.forget_pending();
+        self.written_revision = None;
+        self.pending_revision = None;
+    }
+}
@@ -0,0 +1,46 @@
+use super::{
+    layout::Rect,
+    statusbar::StatusBar,
+    terminal::{Coords, Size},
+    view::View,
+};
+
+/// A view onto a buffer together with its own status line, occupying one
+/// rectangle of the screen.
+pub struct Window {
+    pub id: usize,
+    pub view: View,
+    statusbar: StatusBar,
+    rect: Rect,
+}
+
+impl Window {
+    pub fn new(id: usize, view: View) -> Self {
+        Self {
+            id,
+            view,
+            statusbar: StatusBar::new(),
+            rect: Rect::default(),
+        }
+    }
+
+    pub const fn rect(&self) -> Rect {
+        self.rect
+    }
+
+    /// Moves the window to `rect`, the bottom row of which goes to the
+    /// status line.
+    pub fn set_rect(&mut self, rect: Rect) {
+        let Rect { origin, size } = rect;
+        let text_height = size.height.saturating_sub(1);
+        self.view.resize(origin, Size { width: size.width, height: text_height });
+        self.statusbar.resize(Coords { row: origin.row + text_height, col: origin.col }, size.width);
+        self.rect = rect;
+    }
+
+    pub fn render(&mut self) {
+        self.view.render();
+        self.statusbar.update_status(self.view.get_status());
+        self.statusbar.render();
+    }
+}
@@ -0,0 +1,28 @@
+//! vih, a small modal text editor for the terminal, in the manner of vi.
+//!
+//! Running the editor takes an [`Editor`]: [`Editor::new`] sets the
+//! terminal up and [`Editor::run`] handles keys until it quits, while
+//! [`Editor::replay`] types a script of keys into it without a terminal.
+//! [`Editor::headless`] makes one that draws to memory instead, and
+//! [`Editor::execute`] runs a `:` command in either, as in
+//! `editor.execute("split notes.txt")`.
+//!
+//! What it edits can be used on its own as well. A [`Buffer`] holds the
+//! [`Line`]s of a file, which are edited a grapheme at a time at a
+//! [`Location`], and a [`Motion`] works out where the caret goes from one.
+//! Key events are read as [`EditorCommand`]s, and [`parse_keys`] turns a
+//! script such as `ihello<Esc>:wq<CR>` into the events it stands for.
+//!
+//! A [`Buffer`] is changed only through its edit methods, which keep its
+//! highlighting and modified flag in step, and written with
+//! [`Buffer::save`]. The edits made after a [`Buffer::checkpoint`] are
+//! taken back together by [`Buffer::undo`] and made again by
+//! [`Buffer::redo`]. A read-only buffer ignores edits, and a very large file
+//! is read a window at a time: [`Buffer::ensure_loaded`] has to be called
+//! before its lines can be looked at.
+#![warn(clippy::all, clippy::pedantic, clippy::print_stdout)]
+mod editor;
+
+pub use editor::{
+    parse_keys, Buffer, Coords, Direction, Editor, EditorCommand, Line, LineEnding, Location, Motion, Size,
+};
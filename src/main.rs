@@ -1,7 +1,62 @@
 #![warn(clippy::all, clippy::pedantic, clippy::print_stdout)]
-mod editor;
-use editor::Editor;
+use std::io::{stdout, Write};
+use std::process::ExitCode;
+use vih::{Editor, Size};
 
-fn main() {
-    Editor::new().unwrap().run();
+/// The screen size scripts are replayed on unless `--size` says otherwise.
+const REPLAY_SIZE: (usize, usize) = (80, 24);
+
+const USAGE: &str = "usage: vih [file ...]
+       vih --replay <keys> [--dump-screen] [--size <width>x<height>] [file ...]";
+
+/// Runs the editor on the files given, or, with `--replay`, types the keys
+/// from a script into it without a terminal and prints the buffer it ends
+/// up with, and the screen too with `--dump-screen`.
+fn main() -> ExitCode {
+    let mut args = std::env::args().skip(1);
+    let mut filenames = Vec::new();
+    let mut script = None;
+    let mut dump_screen = false;
+    let mut size = REPLAY_SIZE;
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--replay" => match args.next() {
+                Some(path) => script = Some(path),
+                None => return usage(),
+            },
+            "--dump-screen" => dump_screen = true,
+            "--size" => match args.next().as_deref().and_then(parse_size) {
+                Some(parsed) => size = parsed,
+                None => return usage(),
+            },
+            _ => filenames.push(arg),
+        }
+    }
+    let Some(script) = script else {
+        Editor::new(&filenames).unwrap().run();
+        return ExitCode::SUCCESS;
+    };
+    let keys = match std::fs::read_to_string(&script) {
+        Ok(keys) => keys,
+        Err(err) => {
+            eprintln!("vih: cannot read {script}: {err}");
+            return ExitCode::FAILURE;
+        },
+    };
+    let (width, height) = size;
+    let output = Editor::replay(&filenames, &keys, Size { width, height }, dump_screen);
+    if stdout().write_all(output.as_bytes()).is_err() {
+        return ExitCode::FAILURE;
+    }
+    ExitCode::SUCCESS
+}
+
+fn parse_size(text: &str) -> Option<(usize, usize)> {
+    let (width, height) = text.split_once('x')?;
+    Some((width.parse().ok()?, height.parse().ok()?))
+}
+
+fn usage() -> ExitCode {
+    eprintln!("{USAGE}");
+    ExitCode::FAILURE
 }
@@ -0,0 +1,61 @@
+use std::fs;
+use std::path::PathBuf;
+use vih::{Buffer, Editor, Location, Size};
+
+/// A file in the temporary directory, removed again when dropped.
+struct TempFile(PathBuf);
+
+impl TempFile {
+    fn new(name: &str, contents: &str) -> Self {
+        let path = std::env::temp_dir().join(format!("vih-api-{}-{name}", std::process::id()));
+        fs::write(&path, contents).unwrap();
+        Self(path)
+    }
+
+    fn name(&self) -> String {
+        self.0.to_string_lossy().into_owned()
+    }
+
+    fn contents(&self) -> String {
+        fs::read_to_string(&self.0).unwrap()
+    }
+}
+
+impl Drop for TempFile {
+    fn drop(&mut self) {
+        let _ = fs::remove_file(&self.0);
+    }
+}
+
+#[test]
+fn buffers_undo_by_checkpoint_and_save() {
+    let file = TempFile::new("buffer.txt", "one\n");
+    let mut buffer = Buffer::open(&file.name()).unwrap();
+    buffer.checkpoint(Location::default());
+    let end = buffer.insert_text("zero\n", Location::default());
+    buffer.checkpoint(end);
+    buffer.insert_char('!', end);
+    assert_eq!(buffer.text_lines(), ["zero", "!one"]);
+    assert_eq!(buffer.undo(Location::default()), Some(end));
+    assert_eq!(buffer.text_lines(), ["zero", "one"]);
+    buffer.undo(end);
+    assert_eq!(buffer.text_lines(), ["one"]);
+    assert!(!buffer.is_dirty());
+    buffer.redo(Location::default());
+    buffer.save().unwrap();
+    assert_eq!(file.contents(), "zero\none\n");
+}
+
+#[test]
+fn editors_run_colon_commands() {
+    let first = TempFile::new("first.txt", "first\n");
+    let second = TempFile::new("second.txt", "second\n");
+    let mut editor = Editor::headless(&[first.name()], Size { width: 40, height: 10 });
+    editor.execute(&format!("e {}", second.name()));
+    assert_eq!(editor.buffer().borrow().filename(), second.name());
+    editor.buffer().borrow_mut().insert_text("edited ", Location::default());
+    editor.execute("w");
+    assert_eq!(second.contents(), "edited second\n");
+    editor.execute("b 1");
+    assert_eq!(editor.buffer().borrow().filename(), first.name());
+}